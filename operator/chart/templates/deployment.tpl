@@ -22,6 +22,9 @@ spec:
         - name: operator-{{ include "operator.nuopMode" . }}
           image: "{{ .Values.deployment.image.repository }}:{{ .Values.deployment.image.tag }}"
           imagePullPolicy: {{ .Values.deployment.image.pullPolicy }}
+          ports:
+            - name: metrics
+              containerPort: 9090
           env:
             - name: NUOP_MODE
               value: {{ include "operator.nuopMode" . }}
@@ -35,6 +35,9 @@ rules:
   - apiGroups: ["extensions"]
     resources: ["ingresses"]
     verbs: ["get", "list", "watch", "create", "update", "patch", "delete"]
+  - apiGroups: ["events.k8s.io"]
+    resources: ["events"]
+    verbs: ["create", "patch"]
   - apiGroups: ["kemper.buzz"]
     resources: ["nuoperators"]
     verbs: ["get", "list", "watch", "create", "update", "patch", "delete"]
@@ -16,10 +16,12 @@ in
     src = ../.;
 
     cargoLock.lockFile = ../Cargo.lock;
+    buildFeatures = ["plugin"];
     doCheck = false;
 
     installPhase = ''
       install -m755 -D target/${config}/release/operator $out/bin/operator
+      install -m755 -D target/${config}/release/nu_plugin_nuop $out/bin/nu_plugin_nuop
     '';
 
     meta = {
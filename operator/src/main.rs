@@ -1,24 +1,59 @@
+use clap::Parser;
 use futures::future::try_join_all;
 use kube::Client;
+use operator::nuop::admin;
+use operator::nuop::cli::Cli;
 use operator::nuop::config::find_mappings;
 use operator::nuop::config::find_scripts;
+use operator::nuop::config::get_admin_addr;
 use operator::nuop::config::get_mapping_path;
+use operator::nuop::config::get_metrics_addr;
 use operator::nuop::config::get_script_path;
 use operator::nuop::manager::manager_controller;
+use operator::nuop::metrics;
 use operator::nuop::reconciler::managed::get_managed_controllers;
 use operator::nuop::reconciler::standard::get_standard_controllers;
 use operator::nuop::{logging, util::NuopMode};
-use tracing::{info, instrument};
+use tracing::{info, instrument, warn};
 
 #[instrument]
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    // Subcommands run offline and must not require a kubeconfig
+    if let Some(command) = cli.command {
+        logging::init_cli();
+        return command.execute().await;
+    }
+
     info!("Initializing Kubernetes client");
     let client = Client::try_default().await?;
 
-    logging::init();
+    let telemetry = logging::init();
+
+    let mode = NuopMode::from_env();
+    if let (false, Some(addr)) = (matches!(mode, NuopMode::Init), get_metrics_addr()) {
+        tokio::spawn(async move {
+            if let Err(e) = metrics::serve(addr).await {
+                warn!("Metrics endpoint stopped: {e}");
+            }
+        });
+    }
+
+    if let (false, Some(addr), Some(filter)) = (
+        matches!(mode, NuopMode::Init),
+        get_admin_addr(),
+        logging::log_filter(),
+    ) {
+        tokio::spawn(async move {
+            if let Err(e) = admin::serve(addr, filter).await {
+                warn!("Admin endpoint stopped: {e}");
+            }
+        });
+    }
 
-    let controllers = match NuopMode::from_env() {
+    let controllers = match mode {
         NuopMode::Init => vec![],
         NuopMode::Manager => {
             info!("Starting Manager mode...");
@@ -38,6 +73,12 @@ async fn main() -> anyhow::Result<()> {
         }
     };
 
-    try_join_all(controllers).await?;
+    let result = try_join_all(controllers).await;
+    if let Some(provider) = telemetry
+        && let Err(e) = provider.shutdown()
+    {
+        warn!("Failed to flush traces: {e}");
+    }
+    result?;
     Ok(())
 }
@@ -0,0 +1,36 @@
+use axum::{Router, extract::State, http::StatusCode, routing::get};
+use tokio::net::TcpListener;
+use tracing::info;
+
+use super::logging::LogFilter;
+
+/// Serves the admin endpoints until the process exits:
+///
+/// - `GET /log-filter` returns the current log filter directives
+/// - `PUT /log-filter` replaces them with the request body
+pub async fn serve(addr: String, filter: LogFilter) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(&addr).await?;
+    info!("Serving admin endpoints on {}", addr);
+    axum::serve(listener, router(filter)).await?;
+    Ok(())
+}
+
+pub(crate) fn router(filter: LogFilter) -> Router {
+    Router::new()
+        .route("/log-filter", get(get_log_filter).put(put_log_filter))
+        .with_state(filter)
+}
+
+async fn get_log_filter(State(filter): State<LogFilter>) -> String {
+    format!("{}\n", filter.directives())
+}
+
+async fn put_log_filter(
+    State(filter): State<LogFilter>,
+    directives: String,
+) -> (StatusCode, String) {
+    match filter.set(&directives) {
+        Ok(()) => (StatusCode::OK, format!("{}\n", filter.directives())),
+        Err(e) => (StatusCode::BAD_REQUEST, format!("{e:#}\n")),
+    }
+}
@@ -0,0 +1,46 @@
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+use super::{admin::router, logging::LogFilter};
+
+async fn request(addr: &str, method: &str, body: &str) -> (String, String) {
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let request = format!(
+        "{method} /log-filter HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let status = head.lines().next().unwrap().to_string();
+    (status, body.to_string())
+}
+
+#[tokio::test]
+async fn test_log_filter_endpoint() {
+    let (_layer, filter) = LogFilter::layer("info").unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    tokio::spawn(async move { axum::serve(listener, router(filter)).await });
+
+    assert_eq!(
+        request(&addr, "GET", "").await,
+        ("HTTP/1.1 200 OK".to_string(), "info\n".to_string())
+    );
+    assert_eq!(
+        request(&addr, "PUT", "info,script:cloner=debug\n").await,
+        (
+            "HTTP/1.1 200 OK".to_string(),
+            "info,script:cloner=debug\n".to_string()
+        )
+    );
+
+    let (status, _) = request(&addr, "PUT", "info,operator=loud").await;
+    assert_eq!(status, "HTTP/1.1 400 Bad Request");
+    let (_, body) = request(&addr, "GET", "").await;
+    assert_eq!(body, "info,script:cloner=debug\n");
+}
@@ -0,0 +1,325 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use clap::Args;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::nuop::{
+    config::find_scripts,
+    reconciler::{
+        children::parse_manifests,
+        config::{Config, ReconcileMode},
+        state::CommandResult,
+    },
+};
+
+use super::run::{load_object, run_object, structured_output};
+
+/// Directory next to `mod.nu` holding one directory per test case.
+const TESTS_DIR: &str = "tests";
+const OBJECT_FILE: &str = "object.yaml";
+const CASE_FILE: &str = "case.yaml";
+const GOLDEN_FILE: &str = "expected.yaml";
+
+#[derive(Args, Debug)]
+pub struct TestArgs {
+    /// directory searched for scripts (mod.nu)
+    #[arg(long, default_value = "/scripts")]
+    pub scripts: PathBuf,
+    /// rewrite the golden files with the actual results
+    #[arg(long)]
+    pub update: bool,
+    /// write a JUnit XML report to this file
+    #[arg(long)]
+    pub junit: Option<PathBuf>,
+}
+
+/// Optional settings of a case, read from `case.yaml`.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, rename_all = "camelCase")]
+pub(crate) struct CaseSpec {
+    pub command: Option<String>,
+    pub dry_run: bool,
+    pub params: BTreeMap<String, String>,
+}
+
+/// The recorded result of a case, kept in `expected.yaml`.
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Golden {
+    pub command: String,
+    pub exit_code: i32,
+    /// Manifests printed in declarative and mutate mode, otherwise stdout
+    /// that is a JSON record or list
+    #[serde(default)]
+    pub output: Option<Value>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Case {
+    pub script: PathBuf,
+    /// Name of the script's directory
+    pub suite: String,
+    pub name: String,
+    pub dir: PathBuf,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Outcome {
+    Passed,
+    Updated,
+    Failed(String),
+}
+
+#[derive(Debug)]
+pub(crate) struct CaseResult {
+    pub case: Case,
+    pub outcome: Outcome,
+    pub duration: Duration,
+}
+
+pub(crate) async fn test(args: TestArgs) -> anyhow::Result<()> {
+    let cases = find_cases(&args.scripts);
+    let mut results = Vec::with_capacity(cases.len());
+
+    for case in cases {
+        let started = Instant::now();
+        let outcome = match run_case(&case, args.update).await {
+            Ok(outcome) => outcome,
+            Err(e) => Outcome::Failed(format!("{e:#}")),
+        };
+        match &outcome {
+            Outcome::Passed => println!("ok: {}/{}", case.suite, case.name),
+            Outcome::Updated => println!("updated: {}/{}", case.suite, case.name),
+            Outcome::Failed(message) => {
+                println!("FAILED: {}/{}\n{}", case.suite, case.name, message)
+            }
+        }
+        results.push(CaseResult {
+            case,
+            outcome,
+            duration: started.elapsed(),
+        });
+    }
+
+    if let Some(path) = &args.junit {
+        fs::write(path, junit(&results)).with_context(|| format!("Failed to write {path:?}"))?;
+    }
+
+    let failed = results
+        .iter()
+        .filter(|r| matches!(r.outcome, Outcome::Failed(_)))
+        .count();
+    println!("{} cases, {} failed", results.len(), failed);
+    if failed > 0 {
+        anyhow::bail!("{failed} golden test case(s) failed");
+    }
+    Ok(())
+}
+
+/// Discovers the cases in the `tests` directory next to every script found
+/// by [`find_scripts`], sorted by script and case name.
+pub(crate) fn find_cases(scripts: &Path) -> Vec<Case> {
+    let mut cases = Vec::new();
+    for script in find_scripts(&scripts.to_string_lossy()) {
+        let Some(script_dir) = script.parent() else {
+            continue;
+        };
+        let suite = script_dir
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let Ok(entries) = fs::read_dir(script_dir.join(TESTS_DIR)) else {
+            continue;
+        };
+        for dir in entries.flatten().map(|e| e.path()) {
+            if dir.join(OBJECT_FILE).is_file() {
+                cases.push(Case {
+                    script: script.clone(),
+                    suite: suite.clone(),
+                    name: dir
+                        .file_name()
+                        .map(|n| n.to_string_lossy().to_string())
+                        .unwrap_or_default(),
+                    dir,
+                });
+            }
+        }
+    }
+    cases.sort_by(|a, b| (&a.suite, &a.name).cmp(&(&b.suite, &b.name)));
+    cases
+}
+
+async fn run_case(case: &Case, update: bool) -> anyhow::Result<Outcome> {
+    let spec_path = case.dir.join(CASE_FILE);
+    let spec: CaseSpec = if spec_path.is_file() {
+        serde_yaml::from_str(&fs::read_to_string(&spec_path)?)
+            .with_context(|| format!("Failed to parse {spec_path:?}"))?
+    } else {
+        CaseSpec::default()
+    };
+    let obj = load_object(&case.dir.join(OBJECT_FILE))?;
+
+    let (config, command, result) = run_object(
+        &case.script,
+        &obj,
+        spec.command,
+        spec.dry_run,
+        spec.params.into_iter().collect(),
+    )
+    .await?;
+    let actual = Golden {
+        command,
+        exit_code: result.exit_code,
+        output: golden_output(&config, &result)?,
+    };
+
+    let golden_path = case.dir.join(GOLDEN_FILE);
+    if update {
+        fs::write(&golden_path, serde_yaml::to_string(&actual)?)
+            .with_context(|| format!("Failed to write {golden_path:?}"))?;
+        return Ok(Outcome::Updated);
+    }
+
+    let Ok(expected) = fs::read_to_string(&golden_path) else {
+        return Ok(Outcome::Failed(format!(
+            "missing {GOLDEN_FILE}, run with --update to record it"
+        )));
+    };
+    let expected: Golden = serde_yaml::from_str(&expected)
+        .with_context(|| format!("Failed to parse {golden_path:?}"))?;
+
+    if expected == actual {
+        Ok(Outcome::Passed)
+    } else {
+        let mut message = diff(
+            &serde_yaml::to_string(&expected)?,
+            &serde_yaml::to_string(&actual)?,
+        );
+        if !result.stderr.is_empty() {
+            let _ = write!(message, "stderr:\n{}", result.stderr);
+        }
+        Ok(Outcome::Failed(message))
+    }
+}
+
+/// The part of a script's output compared against the golden file.
+pub(crate) fn golden_output(
+    config: &Config,
+    result: &CommandResult,
+) -> anyhow::Result<Option<Value>> {
+    let succeeded = matches!(result.exit_code, 0 | 2);
+    match config.mode {
+        ReconcileMode::Declarative | ReconcileMode::Mutate if succeeded => {
+            let manifests = parse_manifests(&result.stdout)?;
+            Ok(Some(serde_json::to_value(manifests)?))
+        }
+        _ => Ok(structured_output(&result.stdout)),
+    }
+}
+
+/// A line diff of two renderings, with `-` marking expected and `+` actual
+/// lines.
+pub(crate) fn diff(expected: &str, actual: &str) -> String {
+    let expected: Vec<_> = expected.lines().collect();
+    let actual: Vec<_> = actual.lines().collect();
+
+    // Longest common subsequence lengths of the suffixes
+    let mut lcs = vec![vec![0usize; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            lcs[i][j] = if expected[i] == actual[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut out = String::new();
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            let _ = writeln!(out, "  {}", expected[i]);
+            i += 1;
+            j += 1;
+        } else if i < expected.len() && (j == actual.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            let _ = writeln!(out, "- {}", expected[i]);
+            i += 1;
+        } else {
+            let _ = writeln!(out, "+ {}", actual[j]);
+            j += 1;
+        }
+    }
+    out
+}
+
+/// Renders the results as JUnit XML with one test suite per script.
+pub(crate) fn junit(results: &[CaseResult]) -> String {
+    let mut suites: Vec<(&str, Vec<&CaseResult>)> = Vec::new();
+    for result in results {
+        match suites
+            .iter_mut()
+            .find(|(name, _)| *name == result.case.suite)
+        {
+            Some((_, cases)) => cases.push(result),
+            None => suites.push((&result.case.suite, vec![result])),
+        }
+    }
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites>\n");
+    for (suite, cases) in suites {
+        let failures = cases
+            .iter()
+            .filter(|r| matches!(r.outcome, Outcome::Failed(_)))
+            .count();
+        let time: f64 = cases.iter().map(|r| r.duration.as_secs_f64()).sum();
+        let _ = writeln!(
+            xml,
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" time=\"{:.3}\">",
+            escape(suite),
+            cases.len(),
+            failures,
+            time
+        );
+        for result in cases {
+            let _ = write!(
+                xml,
+                "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\"",
+                escape(&result.case.name),
+                escape(suite),
+                result.duration.as_secs_f64()
+            );
+            match &result.outcome {
+                Outcome::Failed(message) => {
+                    let first = message.lines().next().unwrap_or_default();
+                    let _ = writeln!(
+                        xml,
+                        ">\n      <failure message=\"{}\">{}</failure>\n    </testcase>",
+                        escape(first),
+                        escape(message)
+                    );
+                }
+                _ => xml.push_str("/>\n"),
+            }
+        }
+        xml.push_str("  </testsuite>\n");
+    }
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
@@ -0,0 +1,176 @@
+use std::{fs, path::Path, time::Duration};
+
+use serde_json::json;
+
+use crate::nuop::reconciler::{config::Config, state::CommandResult};
+
+use super::golden::{
+    Case, CaseResult, Golden, Outcome, TestArgs, diff, find_cases, golden_output, junit, test,
+};
+
+// Skip if nu command is not available in PATH
+fn should_skip_script_tests() -> bool {
+    std::process::Command::new("nu")
+        .arg("--version")
+        .output()
+        .is_err()
+}
+
+const OBJECT: &str =
+    "apiVersion: apps/v1\nkind: Deployment\nmetadata:\n  name: web\n  namespace: default\n";
+
+fn write(path: &Path, content: &str) {
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, content).unwrap();
+}
+
+fn result(exit_code: i32, stdout: &str) -> CommandResult {
+    CommandResult {
+        exit_code,
+        stdout: stdout.to_string(),
+        stderr: String::new(),
+    }
+}
+
+fn case_result(suite: &str, name: &str, outcome: Outcome) -> CaseResult {
+    CaseResult {
+        case: Case {
+            script: format!("{suite}/mod.nu").into(),
+            suite: suite.to_string(),
+            name: name.to_string(),
+            dir: format!("{suite}/tests/{name}").into(),
+        },
+        outcome,
+        duration: Duration::from_millis(250),
+    }
+}
+
+#[test]
+fn test_find_cases_next_to_scripts() {
+    let root = tempfile::tempdir().unwrap();
+    write(&root.path().join("b/mod.nu"), "");
+    write(&root.path().join("b/tests/update/object.yaml"), OBJECT);
+    write(&root.path().join("b/tests/create/object.yaml"), OBJECT);
+    write(&root.path().join("b/tests/fixtures/other.yaml"), OBJECT);
+    write(&root.path().join("nested/a/mod.nu"), "");
+    write(&root.path().join("nested/a/tests/only/object.yaml"), OBJECT);
+    write(&root.path().join("c/mod.nu"), "");
+
+    let cases: Vec<_> = find_cases(root.path())
+        .into_iter()
+        .map(|c| format!("{}/{}", c.suite, c.name))
+        .collect();
+    assert_eq!(cases, ["a/only", "b/create", "b/update"]);
+}
+
+#[test]
+fn test_golden_output_by_mode() {
+    let imperative: Config = serde_yaml::from_str("name: t\nversion: v1\nkind: Secret\n").unwrap();
+    assert_eq!(
+        golden_output(&imperative, &result(2, "{\"changed\": true}")).unwrap(),
+        Some(json!({ "changed": true }))
+    );
+    assert_eq!(
+        golden_output(&imperative, &result(0, "Reconciling web")).unwrap(),
+        None
+    );
+
+    let declarative: Config =
+        serde_yaml::from_str("name: t\nversion: v1\nkind: Secret\nmode: declarative\n").unwrap();
+    let manifests = golden_output(
+        &declarative,
+        &result(
+            0,
+            "apiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: child\n",
+        ),
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(manifests[0]["metadata"]["name"], "child");
+    assert_eq!(
+        golden_output(&declarative, &result(1, "not: [yaml")).unwrap(),
+        None
+    );
+}
+
+#[test]
+fn test_diff_marks_changed_lines() {
+    assert_eq!(
+        diff(
+            "command: reconcile\nexitCode: 0\noutput: null\n",
+            "command: reconcile\nexitCode: 2\noutput: null\n"
+        ),
+        "  command: reconcile\n- exitCode: 0\n+ exitCode: 2\n  output: null\n"
+    );
+}
+
+#[test]
+fn test_junit_report() {
+    let xml = junit(&[
+        case_result("secret-cloner", "create", Outcome::Passed),
+        case_result(
+            "secret-cloner",
+            "update",
+            Outcome::Failed("- exitCode: 0\n+ exitCode: <2>".to_string()),
+        ),
+        case_result("replicator", "delete", Outcome::Updated),
+    ]);
+
+    assert!(
+        xml.contains(
+            "<testsuite name=\"secret-cloner\" tests=\"2\" failures=\"1\" time=\"0.500\">"
+        )
+    );
+    assert!(xml.contains("<testcase name=\"create\" classname=\"secret-cloner\" time=\"0.250\"/>"));
+    assert!(xml.contains(
+        "<failure message=\"- exitCode: 0\">- exitCode: 0\n+ exitCode: &lt;2&gt;</failure>"
+    ));
+    assert!(xml.contains("<testsuite name=\"replicator\" tests=\"1\" failures=\"0\""));
+}
+
+#[tokio::test]
+async fn test_update_records_golden_files() {
+    if should_skip_script_tests() {
+        return;
+    }
+
+    let root = tempfile::tempdir().unwrap();
+    let script = root.path().join("deployments/mod.nu");
+    write(
+        &script,
+        &fs::read_to_string(
+            "src/nuop/reconciler/controller_tests/scripts/success-with-changes/mod.nu",
+        )
+        .unwrap(),
+    );
+    write(
+        &root.path().join("deployments/tests/changes/object.yaml"),
+        OBJECT,
+    );
+
+    let args = |update| TestArgs {
+        scripts: root.path().to_path_buf(),
+        update,
+        junit: Some(root.path().join("junit.xml")),
+    };
+
+    assert!(test(args(false)).await.is_err(), "golden file missing");
+    test(args(true)).await.unwrap();
+
+    let golden: Golden = serde_yaml::from_str(
+        &fs::read_to_string(root.path().join("deployments/tests/changes/expected.yaml")).unwrap(),
+    )
+    .unwrap();
+    assert_eq!(
+        golden,
+        Golden {
+            command: "reconcile".to_string(),
+            exit_code: 2,
+            output: None,
+        }
+    );
+
+    test(args(false)).await.unwrap();
+    let xml = fs::read_to_string(root.path().join("junit.xml")).unwrap();
+    assert!(xml.contains("failures=\"0\""));
+}
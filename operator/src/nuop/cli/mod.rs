@@ -0,0 +1,58 @@
+mod golden;
+mod replay;
+mod run;
+mod validate;
+
+use clap::{Parser, Subcommand};
+
+pub use golden::TestArgs;
+pub use replay::ReplayArgs;
+pub use run::RunArgs;
+pub use validate::ValidateArgs;
+
+/// Nushell Operator. Without a subcommand the operator starts in the mode
+/// selected by `NUOP_MODE`.
+#[derive(Parser, Debug)]
+#[command(name = "operator", version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Replay a recorded invocation through a script locally
+    Replay(ReplayArgs),
+    /// Replay an object through a script locally, without a cluster
+    Run(RunArgs),
+    /// Run the golden test cases in each script's tests directory
+    Test(TestArgs),
+    /// Check scripts, mappings and NuOperator manifests for misconfiguration
+    Validate(ValidateArgs),
+}
+
+impl Command {
+    pub async fn execute(self) -> anyhow::Result<()> {
+        match self {
+            Command::Replay(args) => replay::replay(args).await,
+            Command::Run(args) => run::run(args).await,
+            Command::Test(args) => golden::test(args).await,
+            Command::Validate(args) => validate::validate(args).await,
+        }
+    }
+}
+
+pub(crate) fn parse_key_val(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .ok_or_else(|| format!("expected KEY=VALUE, got '{s}'"))
+}
+
+#[cfg(test)]
+mod golden_tests;
+
+#[cfg(test)]
+mod run_tests;
+
+#[cfg(test)]
+mod validate_tests;
@@ -0,0 +1,80 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use clap::Args;
+use kube::api::DynamicObject;
+use serde::Serialize;
+
+use crate::nuop::reconciler::{
+    controller::{action_for_exit_code, execute_delegate},
+    recorder::load_recording,
+    state::ProcessExecutor,
+};
+
+use super::{golden::golden_output, run::config_for_object};
+
+#[derive(Args, Debug)]
+pub struct ReplayArgs {
+    /// recording written to NUOP_RECORD_DIR
+    pub recording: PathBuf,
+    /// script to run instead of the recorded one, e.g. a local checkout
+    #[arg(long)]
+    pub script: Option<PathBuf>,
+    /// command to run instead of the recorded one
+    #[arg(long)]
+    pub command: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ReplayReport {
+    pub command: String,
+    pub exit_code: i32,
+    pub recorded_exit_code: i32,
+    pub action: String,
+    /// Whether stdout differs from the recorded, redacted stdout
+    pub stdout_changed: bool,
+    pub stdout: String,
+    pub stderr: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<serde_json::Value>,
+}
+
+pub(crate) async fn replay(args: ReplayArgs) -> anyhow::Result<()> {
+    let recording = load_recording(&args.recording)?;
+    let obj: DynamicObject = serde_json::from_value(recording.object.clone())
+        .context("Recording does not contain a Kubernetes object")?;
+    let script = args.script.unwrap_or_else(|| recording.script.clone());
+    let command = args.command.unwrap_or_else(|| recording.command.clone());
+
+    // The recorded context carries the params, dry-run flag and lookups of
+    // the original invocation
+    let result = execute_delegate(
+        &ProcessExecutor::from_env(),
+        &script,
+        &obj,
+        &command,
+        &recording.context,
+    )
+    .await?;
+
+    let config = config_for_object(&script, &obj)?;
+    let action = action_for_exit_code(&config, &obj, result.exit_code);
+
+    let report = ReplayReport {
+        command,
+        exit_code: result.exit_code,
+        recorded_exit_code: recording.exit_code,
+        action: match &action {
+            Ok(action) => format!("{action:?}"),
+            Err(e) => format!("error: {e}"),
+        },
+        stdout_changed: result.stdout != recording.stdout,
+        result: golden_output(&config, &result).ok().flatten(),
+        stdout: result.stdout,
+        stderr: result.stderr,
+    };
+    print!("{}", serde_yaml::to_string(&report)?);
+
+    action.map(|_| ()).map_err(Into::into)
+}
@@ -0,0 +1,129 @@
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use clap::Args;
+use kube::api::DynamicObject;
+use serde::Serialize;
+
+use crate::nuop::reconciler::{
+    config::{Config, ReconcilePhase},
+    controller::{action_for_exit_code, execute_delegate},
+    finalizer::detect_phase,
+    state::{CommandResult, InvocationContext, ProcessExecutor},
+    util::{get_script_config, pod_namespace},
+};
+
+use super::{golden::golden_output, parse_key_val};
+
+#[derive(Args, Debug)]
+pub struct RunArgs {
+    /// path to the script's mod.nu
+    pub script: PathBuf,
+    /// YAML or JSON file containing the object to reconcile
+    pub object: PathBuf,
+    /// command to run instead of the one derived from the object
+    #[arg(long)]
+    pub command: Option<String>,
+    /// run the script in dry-run mode
+    #[arg(long)]
+    pub dry_run: bool,
+    /// script parameter as KEY=VALUE, may be repeated
+    #[arg(long = "param", value_parser = parse_key_val)]
+    pub params: Vec<(String, String)>,
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct RunReport {
+    pub command: String,
+    #[serde(rename = "exitCode")]
+    pub exit_code: i32,
+    pub action: String,
+    pub stdout: String,
+    pub stderr: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<serde_json::Value>,
+}
+
+pub(crate) async fn run(args: RunArgs) -> anyhow::Result<()> {
+    let obj = load_object(&args.object)?;
+    let (config, command, result) =
+        run_object(&args.script, &obj, args.command, args.dry_run, args.params).await?;
+
+    let action = action_for_exit_code(&config, &obj, result.exit_code);
+
+    let report = RunReport {
+        command,
+        exit_code: result.exit_code,
+        action: match &action {
+            Ok(action) => format!("{action:?}"),
+            Err(e) => format!("error: {e}"),
+        },
+        result: golden_output(&config, &result).ok().flatten(),
+        stdout: result.stdout,
+        stderr: result.stderr,
+    };
+    print!("{}", serde_yaml::to_string(&report)?);
+
+    action.map(|_| ()).map_err(Into::into)
+}
+
+pub(crate) fn load_object(path: &Path) -> anyhow::Result<DynamicObject> {
+    let file = File::open(path).with_context(|| format!("Failed to open {path:?}"))?;
+    serde_yaml::from_reader(file).with_context(|| format!("Failed to parse object from {path:?}"))
+}
+
+/// Runs a script against an object the way the controller would, returning
+/// the config used, the command run and its result.
+pub(crate) async fn run_object(
+    script: &Path,
+    obj: &DynamicObject,
+    command: Option<String>,
+    dry_run: bool,
+    params: Vec<(String, String)>,
+) -> anyhow::Result<(Config, String, CommandResult)> {
+    let mut config = config_for_object(script, obj)?;
+    config.dry_run |= dry_run;
+    config.params.extend(params);
+
+    let command = command.unwrap_or_else(|| {
+        match detect_phase(obj, &config.finalizers()) {
+            ReconcilePhase::Finalizing(_) => "finalize",
+            _ => "reconcile",
+        }
+        .to_string()
+    });
+
+    let context = InvocationContext::new(&config, obj, pod_namespace(), 1);
+    let result = execute_delegate(
+        &ProcessExecutor::from_env(),
+        script,
+        obj,
+        &command,
+        &context,
+    )
+    .await?;
+
+    Ok((config, command, result))
+}
+
+/// Scripts watching several kinds run with the config of the object's kind.
+pub(crate) fn config_for_object(script: &Path, obj: &DynamicObject) -> anyhow::Result<Config> {
+    let configs = get_script_config(&script.to_path_buf())?.watched();
+    let kind = obj.types.as_ref().map(|t| t.kind.as_str());
+    Ok(configs
+        .iter()
+        .find(|c| Some(c.kind.as_str()) == kind)
+        .unwrap_or(&configs[0])
+        .clone())
+}
+
+/// Script output that parses as a JSON record or list. YAML is not accepted
+/// since ordinary log lines like `Processing: name` are valid YAML maps.
+pub(crate) fn structured_output(stdout: &str) -> Option<serde_json::Value> {
+    serde_json::from_str::<serde_json::Value>(stdout)
+        .ok()
+        .filter(|v| v.is_object() || v.is_array())
+}
@@ -0,0 +1,75 @@
+use std::io::Write;
+use std::path::PathBuf;
+
+use serde_json::json;
+
+use super::{
+    parse_key_val,
+    run::{RunArgs, run, structured_output},
+};
+
+// Skip if nu command is not available in PATH
+fn should_skip_script_tests() -> bool {
+    std::process::Command::new("nu")
+        .arg("--version")
+        .output()
+        .is_err()
+}
+
+#[test]
+fn test_parse_key_val() {
+    assert_eq!(
+        parse_key_val("TARGET=a=b").unwrap(),
+        ("TARGET".to_string(), "a=b".to_string())
+    );
+    assert!(parse_key_val("TARGET").is_err());
+}
+
+#[test]
+fn test_structured_output() {
+    assert_eq!(
+        structured_output(r#"{"name": "test", "replicas": 2}"#),
+        Some(json!({"name": "test", "replicas": 2}))
+    );
+    assert_eq!(
+        structured_output(r#"[{"kind": "ConfigMap"}]"#),
+        Some(json!([{"kind": "ConfigMap"}]))
+    );
+    assert_eq!(structured_output("Reconciling: test-deployment"), None);
+    assert_eq!(structured_output("\"plain string\""), None);
+    assert_eq!(structured_output(""), None);
+}
+
+#[tokio::test]
+async fn test_run_replays_object_without_cluster() {
+    if should_skip_script_tests() {
+        return;
+    }
+
+    let mut object = tempfile::NamedTempFile::new().unwrap();
+    writeln!(
+        object,
+        r#"{{"apiVersion": "apps/v1", "kind": "Deployment", "metadata": {{"name": "test-deployment", "namespace": "default"}}}}"#
+    )
+    .unwrap();
+
+    let args = RunArgs {
+        script: PathBuf::from(
+            "src/nuop/reconciler/controller_tests/scripts/success-with-changes/mod.nu",
+        ),
+        object: object.path().to_path_buf(),
+        command: None,
+        dry_run: false,
+        params: vec![],
+    };
+    assert!(run(args).await.is_ok());
+
+    let args = RunArgs {
+        script: PathBuf::from("src/nuop/reconciler/controller_tests/scripts/error/mod.nu"),
+        object: object.path().to_path_buf(),
+        command: None,
+        dry_run: false,
+        params: vec![],
+    };
+    assert!(run(args).await.is_err());
+}
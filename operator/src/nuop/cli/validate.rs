@@ -0,0 +1,288 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Formatter},
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::Context;
+use clap::Args;
+
+use crate::nuop::{
+    config::{find_mappings, find_scripts},
+    manager::{Mapping, NuOperator},
+    reconciler::{
+        config::{Config, field_selector_errors, label_selector_errors, lookup_errors},
+        filter::filter_errors,
+        predicate::predicate_errors,
+        util::get_script_config,
+    },
+};
+
+#[derive(Args, Debug)]
+pub struct ValidateArgs {
+    /// directory searched for scripts (mod.nu)
+    #[arg(long, default_value = "/scripts")]
+    pub scripts: PathBuf,
+    /// directory searched for mapping files (*.yaml)
+    #[arg(long)]
+    pub mappings: Option<PathBuf>,
+    /// NuOperator manifest whose mappings are checked, may be repeated
+    #[arg(long = "nuoperator")]
+    pub nuoperators: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Finding {
+    pub severity: Severity,
+    pub subject: String,
+    pub message: String,
+}
+
+impl Finding {
+    fn error(subject: impl Display, message: impl Into<String>) -> Self {
+        Finding {
+            severity: Severity::Error,
+            subject: subject.to_string(),
+            message: message.into(),
+        }
+    }
+
+    fn warning(subject: impl Display, message: impl Into<String>) -> Self {
+        Finding {
+            severity: Severity::Warning,
+            subject: subject.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let level = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{level}: {}: {}", self.subject, self.message)
+    }
+}
+
+pub(crate) async fn validate(args: ValidateArgs) -> anyhow::Result<()> {
+    let scripts: Vec<(PathBuf, Result<Config, String>)> =
+        find_scripts(&args.scripts.to_string_lossy())
+            .into_iter()
+            .map(|script| {
+                let config = check_syntax(&script)
+                    .and_then(|_| get_script_config(&script).map_err(|e| format!("{e:#}")));
+                (script, config)
+            })
+            .collect();
+
+    let mut mappings: Vec<(String, Result<Mapping, String>)> = Vec::new();
+    if let Some(dir) = &args.mappings {
+        for path in find_mappings(&dir.to_string_lossy()) {
+            mappings.push((path.display().to_string(), load_yaml(&path)));
+        }
+    }
+    for path in &args.nuoperators {
+        match load_yaml::<NuOperator>(path) {
+            Ok(nuoperator) => mappings.extend(
+                nuoperator
+                    .spec
+                    .mappings
+                    .into_iter()
+                    .map(|m| (format!("{}#{}", path.display(), m.name), Ok(m))),
+            ),
+            Err(e) => mappings.push((path.display().to_string(), Err(e))),
+        }
+    }
+
+    let findings = check(&scripts, &mappings);
+    for finding in &findings {
+        println!("{finding}");
+    }
+
+    let errors = findings
+        .iter()
+        .filter(|f| f.severity == Severity::Error)
+        .count();
+    println!(
+        "{} script(s), {} mapping(s) checked: {} error(s), {} warning(s)",
+        scripts.len(),
+        mappings.len(),
+        errors,
+        findings.len() - errors
+    );
+
+    if errors > 0 {
+        anyhow::bail!("validation failed with {errors} error(s)");
+    }
+    Ok(())
+}
+
+/// Reports problems the operator would otherwise only log at runtime. Scripts
+/// may share a kind, but not a finalizer on it; with mappings, only mapped
+/// scripts are compared, as in managed mode.
+pub(crate) fn check(
+    scripts: &[(PathBuf, Result<Config, String>)],
+    mappings: &[(String, Result<Mapping, String>)],
+) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    // Scripts watching several kinds are checked once per kind
+    let configs: Vec<(&PathBuf, Config)> = scripts
+        .iter()
+        .filter_map(|(script, config)| match config {
+            Ok(config) => Some((script, config)),
+            Err(e) => {
+                findings.push(Finding::error(script.display(), e.clone()));
+                None
+            }
+        })
+        .flat_map(|(script, config)| config.watched().into_iter().map(move |c| (script, c)))
+        .collect();
+
+    let mappings: Vec<(&String, &Mapping)> = mappings
+        .iter()
+        .filter_map(|(source, mapping)| match mapping {
+            Ok(mapping) => Some((source, mapping)),
+            Err(e) => {
+                findings.push(Finding::error(source, e.clone()));
+                None
+            }
+        })
+        .collect();
+
+    for (script, config) in &configs {
+        if config.kind.is_empty() || config.version.is_empty() {
+            findings.push(Finding::error(
+                script.display(),
+                format!(
+                    "script '{}' declares neither a kind nor watches",
+                    config.name
+                ),
+            ));
+        }
+        for error in label_selector_errors(&config.label_selectors, config.label_selector.as_ref())
+            .into_iter()
+            .chain(field_selector_errors(&config.field_selectors))
+            .chain(predicate_errors(&config.predicates))
+            .chain(filter_errors(config.filter.as_deref()))
+            .chain(lookup_errors(&config.lookups))
+        {
+            let finding = Finding::error(script.display(), error);
+            if !findings.contains(&finding) {
+                findings.push(finding);
+            }
+        }
+    }
+
+    for (source, mapping) in &mappings {
+        for error in
+            label_selector_errors(&mapping.label_selectors, mapping.label_selector.as_ref())
+                .into_iter()
+                .chain(field_selector_errors(&mapping.field_selectors))
+                .chain(predicate_errors(&mapping.predicates))
+                .chain(filter_errors(mapping.filter.as_deref()))
+        {
+            findings.push(Finding::error(source, error));
+        }
+        if !configs.iter().any(|(_, config)| selects(mapping, config)) {
+            findings.push(Finding::error(
+                source,
+                format!(
+                    "mapping '{}' ({}/{}/{}) matches no script",
+                    mapping.name, mapping.group, mapping.version, mapping.kind
+                ),
+            ));
+        }
+    }
+
+    let mut claimed: HashMap<(String, String, String, &str), &PathBuf> = HashMap::new();
+    for (script, config) in &configs {
+        if !mappings.is_empty() && !mappings.iter().any(|(_, m)| selects(m, config)) {
+            findings.push(Finding::warning(
+                script.display(),
+                format!("script '{}' is not selected by any mapping", config.name),
+            ));
+            continue;
+        }
+
+        for finalizer in config.finalizers() {
+            let key = (
+                config.group.clone(),
+                config.version.clone(),
+                config.kind.clone(),
+                finalizer,
+            );
+            if let Some(first) = claimed.get(&key) {
+                findings.push(Finding::error(
+                    script.display(),
+                    format!(
+                        "finalizer {} on kind {} already claimed by {}",
+                        finalizer,
+                        config.kind,
+                        first.display()
+                    ),
+                ));
+            } else {
+                claimed.insert(key, script);
+            }
+        }
+    }
+
+    findings
+}
+
+fn selects(mapping: &Mapping, config: &Config) -> bool {
+    mapping.name == config.name
+        && mapping.group == config.group
+        && mapping.kind == config.kind
+        && mapping.version == config.version
+}
+
+/// Variable carrying the script path to `nu-check`, so the path is never
+/// parsed as nu code.
+const CHECK_PATH: &str = "NUOP_CHECK_PATH";
+
+pub(crate) fn syntax_check_command(script: &Path) -> Command {
+    let mut command = Command::new("nu");
+    command
+        .arg("--commands")
+        .arg(format!("nu-check --debug $env.{CHECK_PATH}"))
+        .env(CHECK_PATH, script);
+    command
+}
+
+fn check_syntax(script: &Path) -> Result<(), String> {
+    let output = syntax_check_command(script)
+        .output()
+        .map_err(|e| format!("failed to run nu: {e}"))?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "parse error: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+    }
+}
+
+fn load_yaml<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T, String> {
+    File::open(path)
+        .with_context(|| format!("failed to open {path:?}"))
+        .and_then(|f| {
+            serde_yaml::from_reader(BufReader::new(f))
+                .with_context(|| format!("failed to parse {path:?}"))
+        })
+        .map_err(|e| format!("{e:#}"))
+}
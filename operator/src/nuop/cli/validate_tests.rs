@@ -0,0 +1,225 @@
+use std::path::PathBuf;
+
+use crate::nuop::{manager::Mapping, reconciler::config::Config};
+
+use super::validate::{Severity, check, syntax_check_command};
+
+fn config(yaml: &str) -> Result<Config, String> {
+    Ok(serde_yaml::from_str(yaml).unwrap())
+}
+
+fn mapping(yaml: &str) -> Result<Mapping, String> {
+    Ok(serde_yaml::from_str(yaml).unwrap())
+}
+
+fn messages(findings: &[super::validate::Finding], severity: Severity) -> Vec<String> {
+    findings
+        .iter()
+        .filter(|f| f.severity == severity)
+        .map(|f| format!("{}: {}", f.subject, f.message))
+        .collect()
+}
+
+#[test]
+fn test_valid_standard_scripts() {
+    let scripts = vec![
+        (
+            PathBuf::from("a/mod.nu"),
+            config(
+                "{name: a, version: v1, kind: Secret, labelSelectors: {app.kubernetes.io/replicate: 'yes'}}",
+            ),
+        ),
+        (
+            PathBuf::from("b/mod.nu"),
+            config(
+                "{name: b, version: v1, kind: ConfigMap, fieldSelectors: {metadata.name: test}}",
+            ),
+        ),
+    ];
+
+    assert!(check(&scripts, &[]).is_empty());
+}
+
+#[test]
+fn test_script_errors_and_finalizer_clashes() {
+    let scripts = vec![
+        (
+            PathBuf::from("a/mod.nu"),
+            config("{name: a, version: v1, kind: Secret, finalizer: example.com/cleanup}"),
+        ),
+        (
+            PathBuf::from("b/mod.nu"),
+            config(
+                "{name: b, version: v1, kind: Secret, finalizers: [example.com/audit, example.com/cleanup]}",
+            ),
+        ),
+        (
+            PathBuf::from("c/mod.nu"),
+            Err("parse error: unexpected end of input".to_string()),
+        ),
+        // Other kinds and other finalizers on the same kind are fine
+        (
+            PathBuf::from("d/mod.nu"),
+            config(
+                "{name: d, group: other, version: v2, kind: Secret, finalizer: example.com/cleanup}",
+            ),
+        ),
+        (
+            PathBuf::from("e/mod.nu"),
+            config("{name: e, version: v1, kind: Secret, finalizer: example.com/backup}"),
+        ),
+    ];
+
+    let findings = check(&scripts, &[]);
+    assert_eq!(
+        messages(&findings, Severity::Error),
+        vec![
+            "c/mod.nu: parse error: unexpected end of input",
+            "b/mod.nu: finalizer example.com/cleanup on kind Secret already claimed by a/mod.nu",
+        ]
+    );
+}
+
+#[test]
+fn test_invalid_selectors() {
+    let scripts = vec![(
+        PathBuf::from("a/mod.nu"),
+        config(
+            "{name: a, version: v1, kind: Pod, labelSelectors: {'bad key!': ok, app: '-x'}, fieldSelectors: {'spec..nodeName': n}}",
+        ),
+    )];
+
+    let findings = check(&scripts, &[]);
+    assert_eq!(
+        messages(&findings, Severity::Error),
+        vec![
+            "a/mod.nu: invalid label selector value '-x' for 'app'",
+            "a/mod.nu: invalid label selector key 'bad key!'",
+            "a/mod.nu: invalid field selector key 'spec..nodeName'",
+        ]
+    );
+}
+
+#[test]
+fn test_invalid_match_expressions() {
+    let mappings = vec![(
+        "m.yaml".to_string(),
+        mapping(
+            "{name: a, version: v1, kind: Pod, labelSelector: {matchExpressions: [{key: env, operator: In}, {key: legacy, operator: Exists, values: [x]}, {key: tier, operator: Like, values: [a]}]}}",
+        ),
+    )];
+    let scripts = vec![(
+        PathBuf::from("a/mod.nu"),
+        config("{name: a, version: v1, kind: Pod}"),
+    )];
+
+    let findings = check(&scripts, &mappings);
+    assert_eq!(
+        messages(&findings, Severity::Error),
+        vec![
+            "m.yaml: match expression 'env In' requires values",
+            "m.yaml: match expression 'legacy Exists' must not have values",
+            "m.yaml: unknown match expression operator 'Like' for 'tier', expected In, NotIn, Exists or DoesNotExist",
+        ]
+    );
+}
+
+#[test]
+fn test_scripts_with_watches() {
+    let scripts = vec![
+        (
+            PathBuf::from("a/mod.nu"),
+            config(
+                "{name: a, labelSelectors: {'bad key!': x}, watches: [{version: v1, kind: Secret}, {version: v1, kind: ConfigMap}]}",
+            ),
+        ),
+        (
+            PathBuf::from("b/mod.nu"),
+            config("{name: b, version: v1, kind: ConfigMap}"),
+        ),
+        (PathBuf::from("c/mod.nu"), config("{name: c}")),
+    ];
+
+    let findings = check(&scripts, &[]);
+    assert_eq!(
+        messages(&findings, Severity::Error),
+        vec![
+            "a/mod.nu: invalid label selector key 'bad key!'",
+            "c/mod.nu: script 'c' declares neither a kind nor watches",
+        ]
+    );
+
+    // Mappings select single watches of a script
+    let mappings = vec![(
+        "m.yaml".to_string(),
+        mapping("{name: a, version: v1, kind: ConfigMap}"),
+    )];
+    let findings = check(&scripts[..1], &mappings);
+    assert_eq!(
+        messages(&findings, Severity::Warning),
+        vec!["a/mod.nu: script 'a' is not selected by any mapping"]
+    );
+}
+
+#[test]
+fn test_mappings_against_scripts() {
+    let scripts = vec![
+        (
+            PathBuf::from("a/mod.nu"),
+            config("{name: a, version: v1, kind: Secret}"),
+        ),
+        (
+            PathBuf::from("b/mod.nu"),
+            config("{name: b, version: v1, kind: ConfigMap}"),
+        ),
+        (
+            PathBuf::from("c/mod.nu"),
+            config("{name: c, version: v1, kind: Secret}"),
+        ),
+    ];
+    let mappings = vec![
+        (
+            "a.yaml".to_string(),
+            mapping("{name: a, version: v1, kind: Secret}"),
+        ),
+        (
+            "c.yaml".to_string(),
+            mapping("{name: c, version: v1, kind: Secret}"),
+        ),
+        (
+            "x.yaml".to_string(),
+            mapping("{name: x, version: v1, kind: Pod}"),
+        ),
+        (
+            "broken.yaml".to_string(),
+            Err("failed to parse".to_string()),
+        ),
+    ];
+
+    let findings = check(&scripts, &mappings);
+    assert_eq!(
+        messages(&findings, Severity::Error),
+        vec![
+            "broken.yaml: failed to parse",
+            "x.yaml: mapping 'x' (/v1/Pod) matches no script",
+        ]
+    );
+    assert_eq!(
+        messages(&findings, Severity::Warning),
+        vec!["b/mod.nu: script 'b' is not selected by any mapping"]
+    );
+}
+
+#[test]
+fn test_syntax_check_keeps_path_out_of_the_command() {
+    let script = PathBuf::from("scripts/it's; rm -rf ~/mod.nu");
+    let command = syntax_check_command(&script);
+
+    let args: Vec<_> = command.get_args().collect();
+    assert!(args.iter().all(|a| !a.to_string_lossy().contains("rm -rf")));
+    assert!(
+        command
+            .get_envs()
+            .any(|(_, value)| value == Some(script.as_os_str()))
+    );
+}
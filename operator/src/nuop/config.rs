@@ -1,8 +1,75 @@
 use std::{env, fs, path::PathBuf};
 
+use super::constants::{
+    NUOP_ADMIN_ADDR, NUOP_DRY_RUN, NUOP_METRICS_ADDR, NUOP_RECORD_DIR, NUOP_RECORD_LIMIT,
+    NUOP_REDACT_PATHS, NUOP_SECRETS_PATH,
+};
+
 pub const NUOP_SCRIPT_PATH: &str = "NUOP_SCRIPT_PATH";
 pub const NUOP_MAPPINGS_PATH: &str = "NUOP_MAPPINGS_PATH";
 
+/// Global dry-run switch, applied to every controller on top of the script
+/// and mapping settings.
+pub fn get_dry_run() -> bool {
+    env::var(NUOP_DRY_RUN).is_ok_and(|v| v.eq_ignore_ascii_case("true"))
+}
+
+/// Address of the metrics endpoint; an empty value disables it.
+pub fn get_metrics_addr() -> Option<String> {
+    match env::var(NUOP_METRICS_ADDR) {
+        Ok(addr) if addr.is_empty() => None,
+        Ok(addr) => Some(addr),
+        Err(_) => Some("0.0.0.0:9090".to_string()),
+    }
+}
+
+/// Address of the admin endpoint; an empty value disables it. Only
+/// reachable from within the pod by default, e.g. via `kubectl port-forward`.
+pub fn get_admin_addr() -> Option<String> {
+    match env::var(NUOP_ADMIN_ADDR) {
+        Ok(addr) if addr.is_empty() => None,
+        Ok(addr) => Some(addr),
+        Err(_) => Some("127.0.0.1:9091".to_string()),
+    }
+}
+
+/// Directory invocations are recorded to; recording is off when unset.
+pub fn get_record_dir() -> Option<PathBuf> {
+    env::var(NUOP_RECORD_DIR)
+        .ok()
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Number of recordings kept, the oldest are removed first.
+pub fn get_record_limit() -> usize {
+    env::var(NUOP_RECORD_LIMIT)
+        .ok()
+        .and_then(|limit| limit.parse().ok())
+        .unwrap_or(100)
+}
+
+/// Field paths redacted from logged and recorded objects, comma separated.
+pub fn get_redact_paths() -> Vec<String> {
+    env::var(NUOP_REDACT_PATHS)
+        .map(|paths| {
+            paths
+                .split(',')
+                .map(|p| p.trim().to_string())
+                .filter(|p| !p.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Directory the source credentials are mounted to, their values are masked
+/// in script output.
+pub fn get_secrets_path() -> PathBuf {
+    env::var(NUOP_SECRETS_PATH)
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from("/secrets"))
+}
+
 pub fn get_script_path() -> String {
     env::var(NUOP_SCRIPT_PATH).unwrap_or_else(|_| "/scripts".to_string())
 }
@@ -1,5 +1,35 @@
 pub const DEFAULT_IMAGE: &str = "ghcr.io/ck3mp3r/nuop:latest";
 pub const LOG_LEVEL: &str = "LOG_LEVEL";
 pub const LOG_FORMAT: &str = "LOG_FORMAT";
+pub const OTEL_EXPORTER_OTLP_ENDPOINT: &str = "OTEL_EXPORTER_OTLP_ENDPOINT";
+pub const OTEL_EXPORTER_OTLP_TRACES_ENDPOINT: &str = "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT";
+pub const OTEL_SERVICE_NAME: &str = "OTEL_SERVICE_NAME";
 pub const NUOP_SOURCES_CONFIG: &str = "nuop-sources-config";
 pub const NUOP_MAPPING_CONFIG: &str = "nuop-mapping-config";
+
+pub const NUOP_ENV_ALLOWLIST: &str = "NUOP_ENV_ALLOWLIST";
+pub const POD_NAMESPACE: &str = "POD_NAMESPACE";
+pub const NUOP_METRICS_ADDR: &str = "NUOP_METRICS_ADDR";
+pub const NUOP_ADMIN_ADDR: &str = "NUOP_ADMIN_ADDR";
+pub const NUOP_RECORD_DIR: &str = "NUOP_RECORD_DIR";
+pub const NUOP_RECORD_LIMIT: &str = "NUOP_RECORD_LIMIT";
+pub const NUOP_REDACT_PATHS: &str = "NUOP_REDACT_PATHS";
+pub const NUOP_SECRETS_PATH: &str = "NUOP_SECRETS_PATH";
+
+// Variables exposed to every script invocation
+pub const NUOP_SCRIPT_NAME: &str = "NUOP_SCRIPT_NAME";
+pub const NUOP_GROUP: &str = "NUOP_GROUP";
+pub const NUOP_VERSION: &str = "NUOP_VERSION";
+pub const NUOP_KIND: &str = "NUOP_KIND";
+pub const NUOP_OBJECT_NAMESPACE: &str = "NUOP_OBJECT_NAMESPACE";
+pub const NUOP_OBJECT_NAME: &str = "NUOP_OBJECT_NAME";
+pub const NUOP_OBJECT_UID: &str = "NUOP_OBJECT_UID";
+pub const NUOP_POD_NAMESPACE: &str = "NUOP_POD_NAMESPACE";
+pub const NUOP_ATTEMPT: &str = "NUOP_ATTEMPT";
+pub const NUOP_DRY_RUN: &str = "NUOP_DRY_RUN";
+pub const NUOP_TMPDIR: &str = "NUOP_TMPDIR";
+pub const NUOP_FINALIZER: &str = "NUOP_FINALIZER";
+pub const NUOP_LOOKUPS: &str = "NUOP_LOOKUPS";
+// W3C trace context of the script span, only set when traces are exported
+pub const TRACEPARENT: &str = "TRACEPARENT";
+pub const TRACESTATE: &str = "TRACESTATE";
@@ -1,17 +1,234 @@
-use std::{env, str::FromStr};
+use std::{
+    env, fmt, io,
+    sync::{Arc, Mutex, OnceLock},
+};
 
-use tracing::Level;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use serde_json::Value;
+use tracing::{Event, Subscriber, info, warn};
+use tracing_subscriber::{
+    EnvFilter, Layer, Registry,
+    filter::{LevelFilter, ParseError},
+    fmt::{
+        FmtContext, FormatEvent, FormatFields,
+        format::{Format, Json, JsonFields, Writer},
+    },
+    layer::SubscriberExt,
+    registry::LookupSpan,
+    reload,
+    util::SubscriberInitExt,
+};
 
-use super::constants::{LOG_FORMAT, LOG_LEVEL};
+use super::{
+    constants::{LOG_FORMAT, LOG_LEVEL},
+    telemetry::{TRACER_NAME, tracer_provider},
+};
 
-pub fn init() {
-    let log_level = Level::from_str(&env::var(LOG_LEVEL).unwrap_or_else(|_| "INFO".to_string()))
-        .unwrap_or(Level::INFO);
+/// Prefix of the shorthand for directives matching one script's invocations.
+const SCRIPT_PREFIX: &str = "script:";
+
+static LOG_FILTER: OnceLock<LogFilter> = OnceLock::new();
+
+/// Level of the spans exported via OTLP, independent of the log filter so
+/// that `reconcile` and `script` spans are traced at any `LOG_LEVEL`.
+pub const TRACE_LEVEL: LevelFilter = LevelFilter::INFO;
+
+/// The global log filter, whose directives can be replaced at runtime.
+#[derive(Clone)]
+pub struct LogFilter {
+    handle: reload::Handle<EnvFilter, Registry>,
+    /// Directives as given, before the script shorthand is expanded
+    directives: Arc<Mutex<String>>,
+}
+
+impl LogFilter {
+    /// The filter of the log output, to attach to the fmt layer with
+    /// [`Layer::with_filter`], and the handle changing it.
+    pub fn layer(
+        directives: &str,
+    ) -> Result<(reload::Layer<EnvFilter, Registry>, Self), ParseError> {
+        let (layer, handle) = reload::Layer::new(parse_filter(directives)?);
+        let filter = Self {
+            handle,
+            directives: Arc::new(Mutex::new(directives.to_string())),
+        };
+        Ok((layer, filter))
+    }
+
+    pub fn directives(&self) -> String {
+        self.directives.lock().unwrap().clone()
+    }
+
+    /// Replaces the directives, leaving the current ones in place when they
+    /// do not parse.
+    pub fn set(&self, directives: &str) -> anyhow::Result<()> {
+        let directives = directives.trim();
+        let mut current = self.directives.lock().unwrap();
+        self.handle.reload(parse_filter(directives)?)?;
+        *current = directives.to_string();
+        info!("Log filter set to {}", directives);
+        Ok(())
+    }
+}
+
+/// The filter installed by [`init`], if any.
+pub fn log_filter() -> Option<LogFilter> {
+    LOG_FILTER.get().cloned()
+}
+
+/// Parses `EnvFilter` directives such as `info,operator::nuop::reconciler=debug`.
+/// `script:NAME=LEVEL` is short for `[reconcile{script=NAME}]=LEVEL`, setting
+/// the level of everything logged while that script handles an object. An
+/// empty string means `info`.
+pub fn parse_filter(directives: &str) -> Result<EnvFilter, ParseError> {
+    let expanded: Vec<_> = directives
+        .split(',')
+        .map(str::trim)
+        .filter(|d| !d.is_empty())
+        .map(|directive| match directive.strip_prefix(SCRIPT_PREFIX) {
+            Some(script) => match script.rsplit_once('=') {
+                Some((name, level)) => format!("[reconcile{{script={name}}}]={level}"),
+                None => format!("[reconcile{{script={script}}}]"),
+            },
+            None => directive.to_string(),
+        })
+        .collect();
+
+    EnvFilter::builder()
+        .with_default_directive(LevelFilter::INFO.into())
+        .parse(expanded.join(","))
+}
+
+/// Field carrying the extra keys of a script's JSON log record, see
+/// [`log_output`](super::reconciler::script_log::log_output).
+const SCRIPT_FIELDS: &str = "fields";
+
+/// The JSON log format, with the extra keys of script log records merged into
+/// the event's fields so they can be queried like the operator's own.
+pub struct JsonFormat(Format<Json>);
+
+impl<S, N> FormatEvent<S, N> for JsonFormat
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: Writer<'_>,
+        event: &Event<'_>,
+    ) -> fmt::Result {
+        if event.metadata().fields().field(SCRIPT_FIELDS).is_none() {
+            return self.0.format_event(ctx, writer, event);
+        }
+        let mut line = String::new();
+        self.0.format_event(ctx, Writer::new(&mut line), event)?;
+        writeln!(writer, "{}", merge_script_fields(line.trim_end()))
+    }
+}
+
+/// Replaces the serialized record in the event's `fields` with its keys,
+/// keeping the event's own fields on collisions.
+fn merge_script_fields(line: &str) -> String {
+    let Ok(mut value) = serde_json::from_str::<Value>(line) else {
+        return line.to_string();
+    };
+    let Some(Value::Object(fields)) = value.get_mut("fields") else {
+        return line.to_string();
+    };
+    let Some(Value::String(record)) = fields.remove(SCRIPT_FIELDS) else {
+        return line.to_string();
+    };
+    match serde_json::from_str::<Value>(&record) {
+        Ok(Value::Object(record)) => {
+            for (key, value) in record {
+                fields.entry(key).or_insert(value);
+            }
+        }
+        _ => {
+            fields.insert(SCRIPT_FIELDS.to_string(), Value::String(record));
+        }
+    }
+    value.to_string()
+}
+
+/// The JSON fmt layer used when `LOG_FORMAT` is `json`.
+pub fn json_layer<S>() -> tracing_subscriber::fmt::Layer<S, JsonFields, JsonFormat>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    tracing_subscriber::fmt::layer()
+        .fmt_fields(JsonFields::new())
+        .event_format(JsonFormat(Format::default().json()))
+}
+
+/// Installs the log subscriber, exporting spans via OTLP when configured.
+/// `LOG_LEVEL` takes a level or filter directives, see [`parse_filter`].
+/// The returned provider is shut down on exit to flush pending spans.
+pub fn init() -> Option<SdkTracerProvider> {
+    let directives = env::var(LOG_LEVEL).unwrap_or_else(|_| "info".to_string());
     let log_format = env::var(LOG_FORMAT).unwrap_or_else(|_| "plain".to_string());
-    let subscriber_builder = tracing_subscriber::fmt().with_max_level(log_level);
-    if log_format.to_lowercase() == "json" {
-        subscriber_builder.json().init();
+
+    let (filter_layer, filter, filter_error) = match LogFilter::layer(&directives) {
+        Ok((layer, filter)) => (layer, filter, None),
+        Err(e) => {
+            let (layer, filter) = LogFilter::layer("info").expect("default directive is valid");
+            (layer, filter, Some(e))
+        }
+    };
+
+    let fmt = if log_format.to_lowercase() == "json" {
+        json_layer().boxed()
     } else {
-        subscriber_builder.init();
+        tracing_subscriber::fmt::layer().boxed()
+    };
+
+    let (provider, error) = match tracer_provider() {
+        Ok(provider) => (provider, None),
+        Err(e) => (None, Some(e)),
+    };
+    let otel = provider.as_ref().map(|p| {
+        tracing_opentelemetry::layer()
+            .with_tracer(p.tracer(TRACER_NAME))
+            .with_filter(TRACE_LEVEL)
+    });
+
+    tracing_subscriber::registry()
+        .with(fmt.with_filter(filter_layer))
+        .with(otel)
+        .init();
+    let _ = LOG_FILTER.set(filter);
+
+    if let Some(e) = filter_error {
+        warn!(
+            "Invalid {} '{}', logging at info: {}",
+            LOG_LEVEL, directives, e
+        );
+    }
+    if let Some(e) = error {
+        warn!("Not exporting traces: {e:#}");
+    }
+    provider
+}
+
+/// Logging for CLI subcommands, which keep stdout for their own output.
+/// `LOG_LEVEL` is read as in [`init`] but defaults to `warn`.
+pub fn init_cli() {
+    let directives = env::var(LOG_LEVEL).unwrap_or_else(|_| "warn".to_string());
+    let (filter, error) = match parse_filter(&directives) {
+        Ok(filter) => (filter, None),
+        Err(e) => (EnvFilter::new("warn"), Some(e)),
+    };
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(io::stderr)
+        .init();
+
+    if let Some(e) = error {
+        warn!(
+            "Invalid {} '{}', logging at warn: {}",
+            LOG_LEVEL, directives, e
+        );
     }
 }
@@ -0,0 +1,152 @@
+use std::{
+    io,
+    sync::{Arc, Mutex},
+};
+
+use tracing::{Level, debug, info, span};
+use tracing_subscriber::{
+    Layer,
+    layer::{Context, SubscriberExt},
+    registry::LookupSpan,
+    util::SubscriberInitExt,
+};
+
+use super::{
+    logging::{LogFilter, TRACE_LEVEL, json_layer, parse_filter},
+    reconciler::{
+        config::Config,
+        script_log::{log_output, reconcile_span},
+    },
+};
+
+#[derive(Clone, Default)]
+struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl Buffer {
+    fn take(&self) -> String {
+        String::from_utf8(std::mem::take(&mut *self.0.lock().unwrap())).unwrap()
+    }
+}
+
+impl io::Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Collects the names of created spans, standing in for the OTel layer.
+#[derive(Clone, Default)]
+struct Spans(Arc<Mutex<Vec<String>>>);
+
+impl<S> Layer<S> for Spans
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &span::Attributes<'_>, _id: &span::Id, _ctx: Context<'_, S>) {
+        self.0
+            .lock()
+            .unwrap()
+            .push(attrs.metadata().name().to_string());
+    }
+}
+
+fn log_in_script(name: &str) {
+    let config: Config =
+        serde_yaml::from_str(&format!("name: {name}\nversion: v1\nkind: ConfigMap\n")).unwrap();
+    let _entered = reconcile_span(&config, Some("default"), "settings", 1).entered();
+    info!("info from {}", name);
+    debug!("debug from {}", name);
+}
+
+#[test]
+fn test_parse_filter_expands_script_shorthand() {
+    let filter = parse_filter("warn, script:secret-cloner=debug").unwrap();
+    let directives = filter.to_string();
+    assert!(directives.contains("[reconcile{script=secret-cloner}]=debug"));
+    assert!(directives.contains("warn"));
+
+    assert_eq!(parse_filter("").unwrap().to_string(), "info");
+    assert_eq!(
+        parse_filter("operator::nuop=trace").unwrap().to_string(),
+        "operator::nuop=trace"
+    );
+    assert!(parse_filter("info,operator=loud").is_err());
+}
+
+#[test]
+fn test_log_filter_changes_at_runtime() {
+    let buffer = Buffer::default();
+    let (layer, filter) = LogFilter::layer("warn").unwrap();
+    let writer = buffer.clone();
+    let _guard = tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(move || writer.clone())
+                .with_filter(layer),
+        )
+        .set_default();
+
+    log_in_script("cloner");
+    assert_eq!(buffer.take(), "");
+
+    filter.set("warn,script:cloner=debug").unwrap();
+    assert_eq!(filter.directives(), "warn,script:cloner=debug");
+    log_in_script("cloner");
+    log_in_script("replicator");
+    let logs = buffer.take();
+    assert!(logs.contains("info from cloner"));
+    assert!(logs.contains("debug from cloner"));
+    assert!(!logs.contains("replicator"));
+
+    assert!(filter.set("info,operator=loud").is_err());
+    assert_eq!(filter.directives(), "warn,script:cloner=debug");
+}
+
+#[test]
+fn test_spans_are_traced_below_the_log_level() {
+    let buffer = Buffer::default();
+    let spans = Spans::default();
+    let (layer, _filter) = LogFilter::layer("warn").unwrap();
+    let writer = buffer.clone();
+    let _guard = tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(move || writer.clone())
+                .with_filter(layer),
+        )
+        .with(spans.clone().with_filter(TRACE_LEVEL))
+        .set_default();
+
+    log_in_script("cloner");
+    assert_eq!(buffer.take(), "");
+    assert_eq!(*spans.0.lock().unwrap(), vec!["reconcile"]);
+}
+
+#[test]
+fn test_json_format_merges_script_fields() {
+    let buffer = Buffer::default();
+    let writer = buffer.clone();
+    let _guard = tracing_subscriber::registry()
+        .with(json_layer().with_writer(move || writer.clone()))
+        .set_default();
+
+    log_output(
+        "stdout",
+        r#"{"level": "warn", "msg": "quota low", "used": 9, "stream": "mine"}"#,
+        Level::INFO,
+        true,
+    );
+
+    let line: serde_json::Value = serde_json::from_str(&buffer.take()).unwrap();
+    assert_eq!(line["level"], "WARN");
+    assert_eq!(
+        line["fields"],
+        serde_json::json!({ "message": "quota low", "stream": "stdout", "used": 9 })
+    );
+}
@@ -1,5 +1,6 @@
 use crate::nuop::manager::{
-    NuOperator, controller::error_policy, reconciler::reconcile, state::State,
+    Mapping, NuOperator, Params, controller::error_policy, model::NuOperatorSpec,
+    reconciler::reconcile, state::State,
 };
 
 use k8s_openapi::api::core::v1::EnvVar;
@@ -272,6 +273,7 @@ async fn test_reconcile_via_controller_with_custom_spec() {
             mappings: vec![],
             sources: vec![],
             service_account_name: Some("custom-sa".to_string()),
+            dry_run: false,
         },
     });
 
@@ -361,3 +363,46 @@ async fn test_reconcile_via_controller_handles_errors() {
         _ => panic!("Expected KubeError::Api"),
     }
 }
+
+#[tokio::test]
+async fn test_reconcile_rejects_colliding_param_variables() {
+    // The mock handle is dropped, so the reconcile must fail before any request
+    let (mock_svc, _) = pair::<http::Request<Body>, http::Response<Body>>();
+    let client = Client::new(mock_svc, "default");
+    let mapping = |name: &str, param: &str| Mapping {
+        name: name.to_string(),
+        version: "v1".to_string(),
+        kind: "Secret".to_string(),
+        params: Params {
+            env: vec![EnvVar {
+                name: param.to_string(),
+                value: Some("x".to_string()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let nuoperator = Arc::new(NuOperator {
+        metadata: ObjectMeta {
+            name: Some("colliding".to_string()),
+            namespace: Some("default".to_string()),
+            ..Default::default()
+        },
+        spec: NuOperatorSpec {
+            mappings: vec![mapping("a-b", "c"), mapping("a", "b_c")],
+            ..Default::default()
+        },
+    });
+
+    let err = reconcile(nuoperator, Arc::new(State::new(client)))
+        .await
+        .unwrap_err();
+
+    let KubeError::Api(response) = err else {
+        panic!("unexpected error {err:?}");
+    };
+    assert_eq!(response.reason, "InvalidMappings");
+    assert!(response.message.contains("NUOP_PARAM_A_B_C"));
+}
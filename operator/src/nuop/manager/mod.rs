@@ -7,6 +7,7 @@ mod state;
 pub use controller::controller as manager_controller;
 pub use model::Mapping;
 pub use model::NuOperator;
+pub use model::Params;
 pub use model::Source;
 use state::State;
 
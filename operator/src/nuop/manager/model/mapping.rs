@@ -1,7 +1,12 @@
-use std::collections::BTreeMap;
+use std::{
+    collections::{BTreeMap, HashMap},
+    env,
+};
 
+use k8s_openapi::{api::core::v1::EnvVar, apimachinery::pkg::apis::meta::v1::LabelSelector};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use tracing::warn;
 
 #[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default)]
 pub struct Mapping {
@@ -24,8 +29,119 @@ pub struct Mapping {
         skip_serializing_if = "BTreeMap::is_empty"
     )]
     pub(crate) label_selectors: BTreeMap<String, String>,
+    /// set-based label selector (matchLabels and matchExpressions) combined with labelSelectors
+    #[serde(
+        default,
+        rename = "labelSelector",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub(crate) label_selector: Option<LabelSelector>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub requeue_after_change: Option<u64>,
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub requeue_after_noop: Option<u64>,
+    /// run the script without mutations and skip operator writes
+    #[serde(default, rename = "dryRun", skip_serializing_if = "Option::is_none")]
+    pub(crate) dry_run: Option<bool>,
+    /// watch predicates replacing the script's: generation, labels, annotations or a .json.path
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) predicates: Vec<String>,
+    /// CEL expression replacing the script's filter, evaluated against `object`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) filter: Option<String>,
+    /// priority among scripts watching the same kind, higher runs first
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) priority: Option<i32>,
+    /// parameters handed to the script as environment variables
+    #[serde(default, skip_serializing_if = "Params::is_empty")]
+    pub(crate) params: Params,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, Default, PartialEq)]
+pub struct Params {
+    /// literal parameter values
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) values: BTreeMap<String, String>,
+    /// parameters sourced from Secrets, ConfigMaps or pod fields
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) env: Vec<EnvVar>,
+}
+
+impl Params {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.values.is_empty() && self.env.is_empty()
+    }
+}
+
+impl Mapping {
+    /// Name of the container variable carrying a sourced parameter into the
+    /// managed deployment, unique per mapping.
+    pub(crate) fn param_env_name(&self, param: &str) -> String {
+        format!("NUOP_PARAM_{}_{}", self.name, param)
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_uppercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect()
+    }
+
+    /// Resolves literal and sourced parameters into the values handed to the
+    /// script. Sourced values are read from the variables injected by
+    /// [`Mapping::param_env_name`].
+    pub(crate) fn resolve_params(&self) -> BTreeMap<String, String> {
+        let mut params = self.params.values.clone();
+        for var in &self.params.env {
+            match env::var(self.param_env_name(&var.name))
+                .ok()
+                .or_else(|| var.value.clone())
+            {
+                Some(value) => {
+                    params.insert(var.name.clone(), value);
+                }
+                None => warn!(
+                    "Parameter {} for mapping {} could not be resolved",
+                    var.name, self.name
+                ),
+            }
+        }
+        params
+    }
+
+    /// Names of the parameters sourced from Secrets.
+    pub(crate) fn secret_params(&self) -> impl Iterator<Item = &str> {
+        self.params
+            .env
+            .iter()
+            .filter(|var| {
+                var.value_from
+                    .as_ref()
+                    .is_some_and(|source| source.secret_key_ref.is_some())
+            })
+            .map(|var| var.name.as_str())
+    }
+}
+
+/// Rejects sourced parameters whose variables collide, e.g. mapping `a-b`
+/// with param `c` and mapping `a` with param `b_c` both map to
+/// `NUOP_PARAM_A_B_C`.
+pub(crate) fn check_param_env_names(mappings: &[Mapping]) -> Result<(), String> {
+    let mut seen: HashMap<String, (&str, &str)> = HashMap::new();
+    for mapping in mappings {
+        for var in &mapping.params.env {
+            let name = mapping.param_env_name(&var.name);
+            if let Some((other_mapping, other_param)) =
+                seen.insert(name.clone(), (&mapping.name, &var.name))
+            {
+                return Err(format!(
+                    "Parameter {} of mapping {} and parameter {} of mapping {} both map to {}",
+                    var.name, mapping.name, other_param, other_mapping, name
+                ));
+            }
+        }
+    }
+    Ok(())
 }
@@ -2,7 +2,8 @@ mod mapping;
 mod nu_operator;
 mod source;
 
-pub use mapping::Mapping;
+pub(crate) use mapping::check_param_env_names;
+pub use mapping::{Mapping, Params};
 pub use nu_operator::NuOperator;
 pub use source::Source;
 
@@ -29,4 +29,7 @@ pub struct NuOperatorSpec {
     /// service account to use
     #[serde(default, rename = "serviceAccountName")]
     pub(crate) service_account_name: Option<String>,
+    /// run every mapped script in dry-run mode
+    #[serde(default, rename = "dryRun")]
+    pub(crate) dry_run: bool,
 }
@@ -1,19 +1,32 @@
 use std::{sync::Arc, time::Duration};
 
-use k8s_openapi::api::{apps::v1::Deployment, core::v1::ConfigMap};
+use k8s_openapi::api::{
+    apps::v1::Deployment,
+    core::v1::{ConfigMap, EnvVar},
+};
 use kube::{Api, Resource, ResourceExt, api::PatchParams, runtime::controller::Action};
 use sha2::{Digest, Sha256};
+use tracing::instrument;
 
-use crate::nuop::{constants::DEFAULT_IMAGE, util::generate_owner_reference};
+use crate::nuop::{
+    constants::{DEFAULT_IMAGE, NUOP_DRY_RUN},
+    util::{generate_owner_reference, to_kube_error},
+};
 
 use super::{
     NuOperator, State,
+    model::check_param_env_names,
     resources::{
         create_or_patch_config_map, create_or_patch_deployment, deployment::DeploymentMeta,
         field_manager, generate_deployment, generate_mapping_configmap, generate_source_configmap,
     },
 };
 
+#[instrument(
+    name = "manager_reconcile",
+    skip_all,
+    fields(namespace = %obj.namespace().unwrap_or_default(), name = %obj.name_any())
+)]
 pub async fn reconcile(obj: Arc<NuOperator>, ctx: Arc<State>) -> Result<Action, kube::Error> {
     let client = &ctx.client;
     let namespace = obj.namespace().unwrap();
@@ -24,10 +37,19 @@ pub async fn reconcile(obj: Arc<NuOperator>, ctx: Arc<State>) -> Result<Action,
 
     let deployment_name = format!("{name}-nuop");
 
-    let env_vars = obj.spec.env.clone();
+    let mut env_vars = obj.spec.env.clone();
+    if obj.spec.dry_run {
+        env_vars.push(EnvVar {
+            name: NUOP_DRY_RUN.to_string(),
+            value: Some("true".to_string()),
+            ..Default::default()
+        });
+    }
 
     let sources = obj.spec.sources.clone();
     let mappings = obj.spec.mappings.clone();
+    check_param_env_names(&mappings)
+        .map_err(|message| to_kube_error("InvalidMappings", &message, 422))?;
     let service_account_name = obj.spec.service_account_name.clone();
 
     let image = obj
@@ -2,14 +2,16 @@ use std::collections::BTreeMap;
 use std::sync::Arc;
 
 use http::{Request, Response};
-use k8s_openapi::api::core::v1::{ConfigMap, SecretKeySelector};
+use k8s_openapi::api::core::v1::{
+    ConfigMap, ConfigMapKeySelector, EnvVar, EnvVarSource, SecretKeySelector,
+};
 use k8s_openapi::apimachinery::pkg::apis::meta::v1::OwnerReference;
 use kube::api::{Api, PatchParams};
 use kube::{Client, client::Body};
 use tokio::sync::Mutex;
 use tower_test::mock::pair;
 
-use crate::nuop::manager::model::{Credentials, Mapping, Source};
+use crate::nuop::manager::model::{Credentials, Mapping, Params, Source};
 use crate::nuop::manager::resources::{
     create_or_patch_config_map, field_manager, generate_source_configmap,
 };
@@ -253,3 +255,80 @@ fn test_field_manager() {
     let result = field_manager::<ConfigMap>();
     assert_eq!(result, "ConfigMap.v1");
 }
+
+#[test]
+fn test_mapping_params_rendered_and_resolved() {
+    let mappings = vec![Mapping {
+        name: "secret-cloner".to_string(),
+        version: "v1".to_string(),
+        kind: "Secret".to_string(),
+        params: Params {
+            values: BTreeMap::from([("TARGET_METHOD".to_string(), "exclude".to_string())]),
+            env: vec![EnvVar {
+                name: "TARGET_NAMESPACES".to_string(),
+                value: Some("kube-system".to_string()),
+                ..Default::default()
+            }],
+        },
+        ..Default::default()
+    }];
+
+    let configmap = generate_mapping_configmap("test", "default", None, &mappings).unwrap();
+    let data = configmap.data.unwrap();
+    let rendered: Mapping = serde_yaml::from_str(&data["secret-cloner.yaml"]).unwrap();
+    assert_eq!(rendered.params, mappings[0].params);
+
+    assert_eq!(
+        rendered.param_env_name("TARGET_NAMESPACES"),
+        "NUOP_PARAM_SECRET_CLONER_TARGET_NAMESPACES"
+    );
+    assert_eq!(
+        rendered.resolve_params(),
+        BTreeMap::from([
+            ("TARGET_METHOD".to_string(), "exclude".to_string()),
+            ("TARGET_NAMESPACES".to_string(), "kube-system".to_string()),
+        ])
+    );
+}
+
+#[test]
+fn test_mapping_secret_params() {
+    let mapping = Mapping {
+        name: "secret-cloner".to_string(),
+        params: Params {
+            env: vec![
+                EnvVar {
+                    name: "API_TOKEN".to_string(),
+                    value_from: Some(EnvVarSource {
+                        secret_key_ref: Some(SecretKeySelector {
+                            name: "creds".to_string(),
+                            key: "token".to_string(),
+                            ..Default::default()
+                        }),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+                EnvVar {
+                    name: "REGION".to_string(),
+                    value_from: Some(EnvVarSource {
+                        config_map_key_ref: Some(ConfigMapKeySelector {
+                            name: "settings".to_string(),
+                            key: "region".to_string(),
+                            ..Default::default()
+                        }),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    assert_eq!(
+        mapping.secret_params().collect::<Vec<_>>(),
+        vec!["API_TOKEN"]
+    );
+}
@@ -102,6 +102,7 @@ pub(crate) fn generate_deployment(
                                 ..Default::default()
                             })
                             .chain(env_vars.to_vec())
+                            .chain(generate_param_env_vars(mappings))
                             .collect::<Vec<EnvVar>>(),
                         ),
                         volume_mounts: Some(volume_mounts),
@@ -116,6 +117,18 @@ pub(crate) fn generate_deployment(
     }
 }
 
+fn generate_param_env_vars(mappings: &[Mapping]) -> Vec<EnvVar> {
+    mappings
+        .iter()
+        .flat_map(|mapping| {
+            mapping.params.env.iter().map(|var| EnvVar {
+                name: mapping.param_env_name(&var.name),
+                ..var.clone()
+            })
+        })
+        .collect()
+}
+
 pub(crate) fn has_drifted(existing: &Deployment, desired: &Deployment) -> bool {
     let existing_spec = existing.spec.as_ref();
     let desired_spec = desired.spec.as_ref();
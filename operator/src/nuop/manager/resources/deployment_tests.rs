@@ -1,5 +1,5 @@
 use crate::nuop::{
-    manager::model::{Credentials, Mapping, Source},
+    manager::model::{Credentials, Mapping, Params, Source},
     manager::resources::{
         create_or_patch_deployment,
         deployment::{DeploymentMeta, generate_volumes_and_mounts, has_drifted},
@@ -10,7 +10,8 @@ use crate::nuop::{
 
 use k8s_openapi::api::apps::v1::{Deployment, DeploymentSpec};
 use k8s_openapi::api::core::v1::{
-    Container, EnvVar, PodSpec, PodTemplateSpec, SecretKeySelector, Volume, VolumeMount,
+    Container, EnvVar, EnvVarSource, PodSpec, PodTemplateSpec, SecretKeySelector, Volume,
+    VolumeMount,
 };
 use k8s_openapi::apimachinery::pkg::apis::meta::v1::{ObjectMeta, OwnerReference};
 use kube::api::Api;
@@ -605,8 +606,28 @@ fn test_generate_deployment_with_sources_and_mappings() {
         kind: "Deployment".to_string(),
         field_selectors: BTreeMap::from([("metadata.name".to_string(), "test".to_string())]),
         label_selectors: BTreeMap::from([("app".to_string(), "test".to_string())]),
+        label_selector: None,
         requeue_after_change: Some(30),
         requeue_after_noop: Some(60),
+        dry_run: None,
+        predicates: vec![],
+        filter: None,
+        priority: None,
+        params: Params {
+            values: BTreeMap::from([("MODE".to_string(), "include".to_string())]),
+            env: vec![EnvVar {
+                name: "API_TOKEN".to_string(),
+                value_from: Some(EnvVarSource {
+                    secret_key_ref: Some(SecretKeySelector {
+                        name: "api".to_string(),
+                        key: "token".to_string(),
+                        optional: None,
+                    }),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }],
+        },
     }];
 
     let deployment = generate_deployment(
@@ -675,6 +696,17 @@ fn test_generate_deployment_with_sources_and_mappings() {
     assert_eq!(container_env[1].name, "TEST_ENV");
     assert_eq!(container_env[1].value.as_ref().unwrap(), "test-value");
 
+    // Sourced mapping parameters are injected under a per-mapping name
+    assert_eq!(container_env[2].name, "NUOP_PARAM_TEST_MAPPING_API_TOKEN");
+    assert_eq!(
+        container_env[2]
+            .value_from
+            .as_ref()
+            .and_then(|v| v.secret_key_ref.as_ref())
+            .map(|s| s.name.as_str()),
+        Some("api")
+    );
+
     // Verify volumes and mounts are present
     let volumes = pod_spec.volumes.unwrap();
     assert!(!volumes.is_empty());
@@ -763,8 +795,14 @@ fn test_generate_volumes_and_mounts() {
         kind: "Deployment".to_string(),
         field_selectors: BTreeMap::new(),
         label_selectors: BTreeMap::new(),
+        label_selector: None,
         requeue_after_change: None,
         requeue_after_noop: None,
+        dry_run: None,
+        predicates: vec![],
+        filter: None,
+        priority: None,
+        params: Params::default(),
     }];
 
     let (volumes, mounts) = generate_volumes_and_mounts(deployment_name, &sources, &mappings);
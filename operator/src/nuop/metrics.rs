@@ -0,0 +1,114 @@
+use std::sync::LazyLock;
+
+use axum::{Router, http::header, response::IntoResponse, routing::get};
+use prometheus_client::{
+    encoding::{EncodeLabelSet, text::encode},
+    metrics::{counter::Counter, family::Family, gauge::Gauge},
+    registry::Registry,
+};
+use tokio::net::TcpListener;
+use tracing::info;
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct ScriptLabels {
+    pub script: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct ObjectLabels {
+    pub script: String,
+    pub namespace: String,
+    pub name: String,
+}
+
+/// Operator metrics, exposed in the Prometheus text format on `/metrics`.
+pub struct Metrics {
+    registry: Registry,
+    pub finalize_failures: Family<ScriptLabels, Counter>,
+    pub finalize_pending: Family<ObjectLabels, Gauge>,
+    pub finalizers_forced: Family<ScriptLabels, Counter>,
+    pub reconciles_skipped: Family<ScriptLabels, Counter>,
+    pub reconciles_filtered: Family<ScriptLabels, Counter>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        let mut registry = Registry::with_prefix("nuop");
+        let finalize_failures = Family::default();
+        let finalize_pending = Family::default();
+        let finalizers_forced = Family::default();
+        let reconciles_skipped = Family::default();
+        let reconciles_filtered = Family::default();
+
+        registry.register(
+            "finalize_failures",
+            "Failed finalize invocations",
+            finalize_failures.clone(),
+        );
+        registry.register(
+            "finalize_pending",
+            "Objects whose finalizer is blocked by failing finalize invocations",
+            finalize_pending.clone(),
+        );
+        registry.register(
+            "finalizers_forced",
+            "Finalizers removed after the finalize deadline or retry limit was exceeded",
+            finalizers_forced.clone(),
+        );
+        registry.register(
+            "reconciles_skipped",
+            "Reconciles skipped because neither object nor script changed",
+            reconciles_skipped.clone(),
+        );
+        registry.register(
+            "reconciles_filtered",
+            "Reconciles skipped because the object did not match the script's filter",
+            reconciles_filtered.clone(),
+        );
+
+        Metrics {
+            registry,
+            finalize_failures,
+            finalize_pending,
+            finalizers_forced,
+            reconciles_skipped,
+            reconciles_filtered,
+        }
+    }
+}
+
+impl Metrics {
+    pub fn encode(&self) -> String {
+        let mut buffer = String::new();
+        encode(&mut buffer, &self.registry).expect("encoding metrics to a string cannot fail");
+        buffer
+    }
+}
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::default);
+
+pub fn metrics() -> &'static Metrics {
+    &METRICS
+}
+
+/// Serves `/metrics` until the process exits.
+pub async fn serve(addr: String) -> anyhow::Result<()> {
+    let app = Router::new().route(
+        "/metrics",
+        get(|| async {
+            (
+                [(
+                    header::CONTENT_TYPE,
+                    "application/openmetrics-text; version=1.0.0; charset=utf-8",
+                )],
+                metrics().encode(),
+            )
+                .into_response()
+        }),
+    );
+
+    let listener = TcpListener::bind(&addr).await?;
+    info!("Serving metrics on {}", addr);
+    axum::serve(listener, app).await?;
+    Ok(())
+}
@@ -1,6 +1,23 @@
+pub mod admin;
+pub mod cli;
 pub mod config;
 pub mod constants;
 pub mod logging;
 pub mod manager;
+pub mod metrics;
+#[cfg(feature = "plugin")]
+pub mod plugin;
 pub mod reconciler;
+pub mod telemetry;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 pub mod util;
+
+#[cfg(test)]
+mod admin_tests;
+
+#[cfg(test)]
+mod logging_tests;
+
+#[cfg(test)]
+mod telemetry_tests;
@@ -0,0 +1,420 @@
+use std::collections::HashMap;
+
+use kube::{
+    Api, Client,
+    api::{ApiResource, DeleteParams, DynamicObject, ListParams, Patch, PatchParams},
+    core::{GroupVersion, TypeMeta},
+    discovery::{self, ApiCapabilities, Discovery, Scope},
+};
+use nu_plugin::EvaluatedCall;
+use nu_protocol::{Category, Signature, SyntaxShape, Type};
+use serde_json::Value;
+
+use super::value::from_nu;
+
+const KIND: &str = "Kind or plural, e.g. Secret";
+const NAME: &str = "Name of the object";
+const NAMESPACE: &str = "Namespace of the object, defaults to the client's namespace";
+const API_VERSION: &str = "API version of the kind, e.g. apps/v1";
+const IGNORE_NOT_FOUND: &str = "Return nothing instead of failing when the object does not exist";
+
+/// Signatures of all commands provided by the plugin.
+pub(crate) fn signatures() -> Vec<Signature> {
+    let command = |name: &str, description: &str| {
+        Signature::build(name)
+            .description(description)
+            .category(Category::Custom("kubernetes".to_string()))
+    };
+    vec![
+        command("k8s get", "Get a Kubernetes object as a record")
+            .required("kind", SyntaxShape::String, KIND)
+            .required("name", SyntaxShape::String, NAME)
+            .named("namespace", SyntaxShape::String, NAMESPACE, Some('n'))
+            .named("api-version", SyntaxShape::String, API_VERSION, Some('a'))
+            .switch("ignore-not-found", IGNORE_NOT_FOUND, None)
+            .input_output_type(Type::Nothing, Type::Any),
+        command("k8s list", "List Kubernetes objects of a kind")
+            .required("kind", SyntaxShape::String, KIND)
+            .named("namespace", SyntaxShape::String, NAMESPACE, Some('n'))
+            .named("api-version", SyntaxShape::String, API_VERSION, Some('a'))
+            .switch(
+                "all-namespaces",
+                "List objects across all namespaces",
+                Some('A'),
+            )
+            .named(
+                "selector",
+                SyntaxShape::String,
+                "Label selector, e.g. app=web",
+                Some('l'),
+            )
+            .named(
+                "field-selector",
+                SyntaxShape::String,
+                "Field selector, e.g. metadata.name=web",
+                None,
+            )
+            .input_output_type(Type::Nothing, Type::Any),
+        command(
+            "k8s apply",
+            "Server-side apply the piped object or list of objects",
+        )
+        .named("namespace", SyntaxShape::String, NAMESPACE, Some('n'))
+        .named(
+            "field-manager",
+            SyntaxShape::String,
+            "Field manager of the applied fields, defaults to nuop",
+            None,
+        )
+        .switch("force", "Take over fields owned by other managers", None)
+        .input_output_type(Type::Any, Type::Any),
+        command("k8s patch", "Patch a Kubernetes object")
+            .required("kind", SyntaxShape::String, KIND)
+            .required("name", SyntaxShape::String, NAME)
+            .required("patch", SyntaxShape::Any, "Patch document")
+            .named("namespace", SyntaxShape::String, NAMESPACE, Some('n'))
+            .named("api-version", SyntaxShape::String, API_VERSION, Some('a'))
+            .named(
+                "type",
+                SyntaxShape::String,
+                "Patch type: merge (default), strategic or json",
+                Some('t'),
+            )
+            .input_output_type(Type::Nothing, Type::Any),
+        command("k8s delete", "Delete a Kubernetes object")
+            .required("kind", SyntaxShape::String, KIND)
+            .required("name", SyntaxShape::String, NAME)
+            .named("namespace", SyntaxShape::String, NAMESPACE, Some('n'))
+            .named("api-version", SyntaxShape::String, API_VERSION, Some('a'))
+            .switch("ignore-not-found", IGNORE_NOT_FOUND, None)
+            .input_output_type(Type::Nothing, Type::Nothing),
+    ]
+}
+
+fn positional(call: &EvaluatedCall, index: usize) -> Result<String, String> {
+    call.req(index).map_err(|e| e.to_string())
+}
+
+fn flag(call: &EvaluatedCall, name: &str) -> Result<Option<String>, String> {
+    call.get_flag(name).map_err(|e| e.to_string())
+}
+
+fn switch(call: &EvaluatedCall, name: &str) -> bool {
+    call.has_flag(name).unwrap_or_default()
+}
+
+/// Picks the resource named by `kind`, matched case-insensitively against
+/// kinds, then plurals, in discovery order.
+pub(crate) fn select_resource(
+    resources: &[(ApiResource, ApiCapabilities)],
+    kind: &str,
+) -> Option<(ApiResource, ApiCapabilities)> {
+    let by_kind = |(ar, _): &&(ApiResource, ApiCapabilities)| ar.kind.eq_ignore_ascii_case(kind);
+    let by_plural =
+        |(ar, _): &&(ApiResource, ApiCapabilities)| ar.plural.eq_ignore_ascii_case(kind);
+    resources
+        .iter()
+        .find(by_kind)
+        .or_else(|| resources.iter().find(by_plural))
+        .cloned()
+}
+
+/// Runs plugin commands against the cluster. The client and discovery
+/// results are created on first use and kept for the plugin's lifetime.
+#[derive(Default)]
+pub(crate) struct Commands {
+    client: Option<Client>,
+    /// Resources by requested API version, `None` for all groups.
+    resources: HashMap<Option<String>, Vec<(ApiResource, ApiCapabilities)>>,
+    /// Changes skipped in dry run, reported once the command returns
+    notices: Vec<String>,
+}
+
+impl Commands {
+    #[cfg(test)]
+    pub fn with_client(client: Client) -> Self {
+        Self {
+            client: Some(client),
+            ..Default::default()
+        }
+    }
+
+    async fn client(&mut self) -> Result<Client, String> {
+        if self.client.is_none() {
+            let client = Client::try_default()
+                .await
+                .map_err(|e| format!("failed to create Kubernetes client: {e}"))?;
+            self.client = Some(client);
+        }
+        Ok(self.client.clone().expect("client initialized"))
+    }
+
+    /// Runs the named command. With `dry_run` writes are not sent but
+    /// described in [`Commands::take_notices`].
+    pub async fn run(
+        &mut self,
+        name: &str,
+        call: &EvaluatedCall,
+        input: &nu_protocol::Value,
+        dry_run: bool,
+    ) -> Result<Value, String> {
+        match name {
+            "k8s get" => self.get(call).await,
+            "k8s list" => self.list(call).await,
+            "k8s apply" => self.apply(call, input, dry_run).await,
+            "k8s patch" => self.patch(call, dry_run).await,
+            "k8s delete" => self.delete(call, dry_run).await,
+            other => Err(format!("unknown command: {other}")),
+        }
+    }
+
+    pub fn take_notices(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.notices)
+    }
+
+    async fn resource(
+        &mut self,
+        kind: &str,
+        api_version: Option<&str>,
+    ) -> Result<(ApiResource, ApiCapabilities), String> {
+        let client = self.client().await?;
+        let key = api_version.map(str::to_string);
+        if !self.resources.contains_key(&key) {
+            let resources = match api_version {
+                Some(api_version) => {
+                    let gv: GroupVersion = api_version
+                        .parse()
+                        .map_err(|e| format!("invalid API version {api_version}: {e}"))?;
+                    discovery::pinned_group(&client, &gv)
+                        .await
+                        .map_err(|e| format!("discovery of {api_version} failed: {e}"))?
+                        .versioned_resources(&gv.version)
+                }
+                None => Discovery::new(client)
+                    .run()
+                    .await
+                    .map_err(|e| format!("discovery failed: {e}"))?
+                    .groups()
+                    .flat_map(|group| group.recommended_resources())
+                    .collect(),
+            };
+            self.resources.insert(key.clone(), resources);
+        }
+
+        select_resource(&self.resources[&key], kind).ok_or_else(|| match api_version {
+            Some(api_version) => format!("unknown kind {kind} in {api_version}"),
+            None => format!("unknown kind {kind}"),
+        })
+    }
+
+    /// The API of a kind, scoped to the given or default namespace for
+    /// namespaced kinds.
+    async fn api(
+        &mut self,
+        kind: &str,
+        api_version: Option<&str>,
+        namespace: Option<&str>,
+    ) -> Result<(Api<DynamicObject>, ApiResource), String> {
+        let (ar, caps) = self.resource(kind, api_version).await?;
+        let client = self.client().await?;
+        let api = match (&caps.scope, namespace) {
+            (Scope::Cluster, _) => Api::all_with(client, &ar),
+            (Scope::Namespaced, Some(ns)) => Api::namespaced_with(client, ns, &ar),
+            (Scope::Namespaced, None) => Api::default_namespaced_with(client, &ar),
+        };
+        Ok((api, ar))
+    }
+
+    async fn get(&mut self, call: &EvaluatedCall) -> Result<Value, String> {
+        let kind = positional(call, 0)?;
+        let name = positional(call, 1)?;
+        let (api, ar) = self
+            .api(
+                &kind,
+                flag(call, "api-version")?.as_deref(),
+                flag(call, "namespace")?.as_deref(),
+            )
+            .await?;
+
+        match api.get_opt(&name).await.map_err(|e| e.to_string())? {
+            Some(obj) => to_value(obj, &ar),
+            None if switch(call, "ignore-not-found") => Ok(Value::Null),
+            None => Err(format!("{} {} not found", ar.kind, name)),
+        }
+    }
+
+    async fn list(&mut self, call: &EvaluatedCall) -> Result<Value, String> {
+        let kind = positional(call, 0)?;
+        let (ar, caps) = self
+            .resource(&kind, flag(call, "api-version")?.as_deref())
+            .await?;
+        let client = self.client().await?;
+        let api: Api<DynamicObject> = match (
+            &caps.scope,
+            flag(call, "namespace")?,
+            switch(call, "all-namespaces"),
+        ) {
+            (Scope::Namespaced, Some(ns), false) => Api::namespaced_with(client, &ns, &ar),
+            (Scope::Namespaced, None, false) => Api::default_namespaced_with(client, &ar),
+            _ => Api::all_with(client, &ar),
+        };
+
+        let params = ListParams {
+            label_selector: flag(call, "selector")?,
+            field_selector: flag(call, "field-selector")?,
+            ..ListParams::default()
+        };
+
+        let list = api.list(&params).await.map_err(|e| e.to_string())?;
+        list.items
+            .into_iter()
+            .map(|obj| to_value(obj, &ar))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Value::Array)
+    }
+
+    async fn apply(
+        &mut self,
+        call: &EvaluatedCall,
+        input: &nu_protocol::Value,
+        dry_run: bool,
+    ) -> Result<Value, String> {
+        let field_manager = flag(call, "field-manager")?.unwrap_or_else(|| "nuop".to_string());
+        let mut params = PatchParams::apply(&field_manager);
+        params.force = switch(call, "force");
+
+        match from_nu(input)? {
+            Value::Array(objects) => {
+                let mut applied = Vec::with_capacity(objects.len());
+                for obj in &objects {
+                    applied.push(self.apply_one(call, &params, obj, dry_run).await?);
+                }
+                Ok(Value::Array(applied))
+            }
+            obj @ Value::Object(_) => self.apply_one(call, &params, &obj, dry_run).await,
+            _ => Err("k8s apply expects an object or a list of objects as input".to_string()),
+        }
+    }
+
+    async fn apply_one(
+        &mut self,
+        call: &EvaluatedCall,
+        params: &PatchParams,
+        obj: &Value,
+        dry_run: bool,
+    ) -> Result<Value, String> {
+        let api_version = obj["apiVersion"]
+            .as_str()
+            .ok_or("object without apiVersion")?;
+        let kind = obj["kind"].as_str().ok_or("object without kind")?;
+        let name = obj["metadata"]["name"]
+            .as_str()
+            .ok_or("object without metadata.name")?;
+        let namespace = match obj["metadata"]["namespace"].as_str() {
+            Some(ns) => Some(ns.to_string()),
+            None => flag(call, "namespace")?,
+        };
+
+        let (api, ar) = self
+            .api(kind, Some(api_version), namespace.as_deref())
+            .await?;
+        if dry_run {
+            self.notices.push(format!(
+                "Dry run: would apply {} {}/{} as {}: {}",
+                ar.kind,
+                namespace.unwrap_or_default(),
+                name,
+                params.field_manager.as_deref().unwrap_or_default(),
+                obj
+            ));
+            return Ok(obj.clone());
+        }
+        let applied = api
+            .patch(name, params, &Patch::Apply(obj))
+            .await
+            .map_err(|e| e.to_string())?;
+        to_value(applied, &ar)
+    }
+
+    async fn patch(&mut self, call: &EvaluatedCall, dry_run: bool) -> Result<Value, String> {
+        let kind = positional(call, 0)?;
+        let name = positional(call, 1)?;
+        let document = from_nu(&call.req(2).map_err(|e| e.to_string())?)?;
+        let patch_type = flag(call, "type")?.unwrap_or_else(|| "merge".to_string());
+        let patch = match patch_type.as_str() {
+            "merge" => Patch::Merge(&document),
+            "strategic" => Patch::Strategic(&document),
+            "json" => Patch::Json(
+                serde_json::from_value(document.clone())
+                    .map_err(|e| format!("invalid JSON patch: {e}"))?,
+            ),
+            other => return Err(format!("unknown patch type {other}")),
+        };
+
+        let namespace = flag(call, "namespace")?;
+        let (api, ar) = self
+            .api(
+                &kind,
+                flag(call, "api-version")?.as_deref(),
+                namespace.as_deref(),
+            )
+            .await?;
+        // The object is returned unchanged so pipelines keep working
+        if dry_run {
+            self.notices.push(format!(
+                "Dry run: would {} patch {} {}/{}: {}",
+                patch_type,
+                ar.kind,
+                namespace.unwrap_or_default(),
+                name,
+                document
+            ));
+            let current = api.get(&name).await.map_err(|e| e.to_string())?;
+            return to_value(current, &ar);
+        }
+        let patched = api
+            .patch(&name, &PatchParams::default(), &patch)
+            .await
+            .map_err(|e| e.to_string())?;
+        to_value(patched, &ar)
+    }
+
+    async fn delete(&mut self, call: &EvaluatedCall, dry_run: bool) -> Result<Value, String> {
+        let kind = positional(call, 0)?;
+        let name = positional(call, 1)?;
+        let namespace = flag(call, "namespace")?;
+        let (api, ar) = self
+            .api(
+                &kind,
+                flag(call, "api-version")?.as_deref(),
+                namespace.as_deref(),
+            )
+            .await?;
+        if dry_run {
+            self.notices.push(format!(
+                "Dry run: would delete {} {}/{}",
+                ar.kind,
+                namespace.unwrap_or_default(),
+                name
+            ));
+            return Ok(Value::Null);
+        }
+
+        match api.delete(&name, &DeleteParams::default()).await {
+            Ok(_) => Ok(Value::Null),
+            Err(kube::Error::Api(e)) if e.code == 404 && switch(call, "ignore-not-found") => {
+                Ok(Value::Null)
+            }
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+/// Serializes an object, filling in the type that list items omit.
+fn to_value(mut obj: DynamicObject, ar: &ApiResource) -> Result<Value, String> {
+    obj.types.get_or_insert_with(|| TypeMeta {
+        api_version: ar.api_version.clone(),
+        kind: ar.kind.clone(),
+    });
+    serde_json::to_value(obj).map_err(|e| e.to_string())
+}
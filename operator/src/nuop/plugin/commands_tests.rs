@@ -0,0 +1,286 @@
+use http::{Method, Request, Response};
+use kube::{
+    Client,
+    api::{ApiResource, GroupVersionKind},
+    client::Body,
+    discovery::{ApiCapabilities, Scope},
+};
+use nu_plugin::EvaluatedCall;
+use nu_protocol::{IntoSpanned, Span, Value as NuValue};
+use serde_json::{Value, json};
+use tower_test::mock;
+
+use super::{
+    commands::{Commands, select_resource},
+    value::to_nu,
+};
+
+fn json_response(status: u16, body: Value) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header("content-type", "application/json")
+        .body(Body::from(serde_json::to_vec(&body).unwrap()))
+        .unwrap()
+}
+
+fn core_resources() -> Value {
+    json!({
+        "kind": "APIResourceList",
+        "groupVersion": "v1",
+        "resources": [{
+            "name": "secrets",
+            "singularName": "secret",
+            "namespaced": true,
+            "kind": "Secret",
+            "verbs": ["get", "list", "watch", "create", "update", "patch", "delete"]
+        }]
+    })
+}
+
+fn resource(group: &str, kind: &str, plural: &str) -> (ApiResource, ApiCapabilities) {
+    let gvk = GroupVersionKind::gvk(group, "v1", kind);
+    (
+        ApiResource::from_gvk_with_plural(&gvk, plural),
+        ApiCapabilities {
+            scope: Scope::Namespaced,
+            subresources: vec![],
+            operations: vec![],
+        },
+    )
+}
+
+fn call(positional: &[NuValue], named: &[(&str, NuValue)], flags: &[&str]) -> EvaluatedCall {
+    let head = Span::test_data();
+    let mut call = EvaluatedCall::new(head);
+    for value in positional {
+        call.add_positional(value.clone());
+    }
+    for (name, value) in named {
+        call.add_named((*name).into_spanned(head), value.clone());
+    }
+    for name in flags {
+        call.add_flag((*name).into_spanned(head));
+    }
+    call
+}
+
+#[test]
+fn test_select_resource_by_kind_then_plural() {
+    let resources = vec![
+        resource("", "Event", "events"),
+        resource("events.k8s.io", "Event", "events"),
+        resource("apps", "Deployment", "deployments"),
+    ];
+
+    let (ar, _) = select_resource(&resources, "event").unwrap();
+    assert_eq!(ar.api_version, "v1", "first match in discovery order");
+    let (ar, _) = select_resource(&resources, "Deployments").unwrap();
+    assert_eq!(ar.kind, "Deployment");
+    assert!(select_resource(&resources, "Pod").is_none());
+}
+
+#[tokio::test]
+async fn test_get_secret_in_namespace() {
+    let (mock_service, mut handle) = mock::pair::<Request<Body>, Response<Body>>();
+    let mut commands = Commands::with_client(Client::new(mock_service, "default"));
+
+    let server = tokio::spawn(async move {
+        let (request, send) = handle.next_request().await.expect("discovery not called");
+        assert_eq!(request.uri().path(), "/api/v1");
+        send.send_response(json_response(200, core_resources()));
+
+        let (request, send) = handle.next_request().await.expect("get not called");
+        assert_eq!(request.method(), Method::GET);
+        assert_eq!(
+            request.uri().path(),
+            "/api/v1/namespaces/team-a/secrets/creds"
+        );
+        send.send_response(json_response(
+            200,
+            json!({
+                "apiVersion": "v1",
+                "kind": "Secret",
+                "metadata": { "name": "creds", "namespace": "team-a" },
+                "data": { "token": "c2VjcmV0" }
+            }),
+        ));
+    });
+
+    let secret = commands
+        .run(
+            "k8s get",
+            &call(
+                &[
+                    NuValue::test_string("secret"),
+                    NuValue::test_string("creds"),
+                ],
+                &[
+                    ("namespace", NuValue::test_string("team-a")),
+                    ("api-version", NuValue::test_string("v1")),
+                ],
+                &[],
+            ),
+            &NuValue::test_nothing(),
+            false,
+        )
+        .await
+        .unwrap();
+
+    server.await.unwrap();
+    assert_eq!(secret["kind"], "Secret");
+    assert_eq!(secret["data"]["token"], "c2VjcmV0");
+}
+
+#[tokio::test]
+async fn test_delete_ignores_missing_object_when_asked() {
+    let (mock_service, mut handle) = mock::pair::<Request<Body>, Response<Body>>();
+    let mut commands = Commands::with_client(Client::new(mock_service, "default"));
+
+    let server = tokio::spawn(async move {
+        let (_, send) = handle.next_request().await.expect("discovery not called");
+        send.send_response(json_response(200, core_resources()));
+
+        for _ in 0..2 {
+            let (request, send) = handle.next_request().await.expect("delete not called");
+            assert_eq!(request.method(), Method::DELETE);
+            assert_eq!(
+                request.uri().path(),
+                "/api/v1/namespaces/default/secrets/gone"
+            );
+            send.send_response(json_response(
+                404,
+                json!({
+                    "kind": "Status",
+                    "apiVersion": "v1",
+                    "status": "Failure",
+                    "message": "secrets \"gone\" not found",
+                    "reason": "NotFound",
+                    "code": 404
+                }),
+            ));
+        }
+    });
+
+    let positional = [NuValue::test_string("Secret"), NuValue::test_string("gone")];
+    let version = [("api-version", NuValue::test_string("v1"))];
+    let nothing = NuValue::test_nothing();
+    let ignored = commands
+        .run(
+            "k8s delete",
+            &call(&positional, &version, &["ignore-not-found"]),
+            &nothing,
+            false,
+        )
+        .await;
+    assert_eq!(ignored, Ok(Value::Null));
+
+    let failed = commands
+        .run(
+            "k8s delete",
+            &call(&positional, &version, &[]),
+            &nothing,
+            false,
+        )
+        .await;
+    assert!(failed.unwrap_err().contains("not found"));
+
+    server.await.unwrap();
+}
+
+#[tokio::test]
+async fn test_apply_requires_object_input() {
+    let mut commands = Commands::default();
+    let result = commands
+        .run(
+            "k8s apply",
+            &call(&[], &[], &[]),
+            &NuValue::test_nothing(),
+            false,
+        )
+        .await;
+    assert_eq!(
+        result,
+        Err("k8s apply expects an object or a list of objects as input".to_string())
+    );
+}
+
+#[tokio::test]
+async fn test_dry_run_only_reads() {
+    let (mock_service, mut handle) = mock::pair::<Request<Body>, Response<Body>>();
+    let mut commands = Commands::with_client(Client::new(mock_service, "default"));
+
+    let server = tokio::spawn(async move {
+        let (_, send) = handle.next_request().await.expect("discovery not called");
+        send.send_response(json_response(200, core_resources()));
+
+        let (request, send) = handle.next_request().await.expect("get not called");
+        assert_eq!(request.method(), Method::GET);
+        assert_eq!(
+            request.uri().path(),
+            "/api/v1/namespaces/default/secrets/creds"
+        );
+        send.send_response(json_response(
+            200,
+            json!({
+                "apiVersion": "v1",
+                "kind": "Secret",
+                "metadata": { "name": "creds", "namespace": "default" }
+            }),
+        ));
+        assert!(handle.next_request().await.is_none(), "write sent");
+    });
+
+    let secret = json!({
+        "apiVersion": "v1",
+        "kind": "Secret",
+        "metadata": { "name": "creds" },
+        "stringData": { "token": "secret" }
+    });
+    let applied = commands
+        .run(
+            "k8s apply",
+            &call(&[], &[], &[]),
+            &to_nu(&secret, Span::test_data()),
+            true,
+        )
+        .await;
+    assert_eq!(applied, Ok(secret));
+
+    let creds = [
+        NuValue::test_string("Secret"),
+        NuValue::test_string("creds"),
+    ];
+    let version = [("api-version", NuValue::test_string("v1"))];
+    let mut patch = creds.to_vec();
+    patch.push(to_nu(&json!({ "data": null }), Span::test_data()));
+    let patched = commands
+        .run(
+            "k8s patch",
+            &call(&patch, &version, &[]),
+            &NuValue::test_nothing(),
+            true,
+        )
+        .await
+        .unwrap();
+    assert_eq!(patched["metadata"]["name"], "creds");
+
+    let deleted = commands
+        .run(
+            "k8s delete",
+            &call(&creds, &version, &[]),
+            &NuValue::test_nothing(),
+            true,
+        )
+        .await;
+    assert_eq!(deleted, Ok(Value::Null));
+
+    let notices = commands.take_notices();
+    assert_eq!(notices.len(), 3);
+    assert!(notices[0].starts_with("Dry run: would apply Secret /creds as nuop"));
+    assert!(notices[1].starts_with("Dry run: would merge patch Secret /creds"));
+    assert_eq!(notices[2], "Dry run: would delete Secret /creds");
+    assert!(commands.take_notices().is_empty());
+
+    drop(commands);
+    server.await.unwrap();
+}
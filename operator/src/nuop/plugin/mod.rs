@@ -0,0 +1,116 @@
+//! `nu_plugin_nuop`: a Nushell plugin giving scripts a native Kubernetes
+//! client through the `k8s get`, `k8s list`, `k8s apply`, `k8s patch` and
+//! `k8s delete` commands.
+
+mod commands;
+mod value;
+
+use std::sync::Mutex;
+
+use nu_plugin::{
+    EngineInterface, EvaluatedCall, MsgPackSerializer, Plugin, PluginCommand, SimplePluginCommand,
+    serve_plugin,
+};
+use nu_protocol::{IntoSpanned, LabeledError, PipelineData, Signature, Value};
+use tokio::runtime::Runtime;
+
+use super::constants::NUOP_DRY_RUN;
+use commands::{Commands, signatures};
+use value::to_nu;
+
+/// Entry point of the plugin binary. The protocol is implemented by
+/// `nu-plugin`, built for the Nushell version of the image.
+pub fn serve() -> anyhow::Result<()> {
+    serve_plugin(&NuopPlugin::new()?, MsgPackSerializer);
+    Ok(())
+}
+
+pub struct NuopPlugin {
+    runtime: Runtime,
+    commands: Mutex<Commands>,
+}
+
+impl NuopPlugin {
+    pub fn new() -> anyhow::Result<Self> {
+        Ok(Self {
+            runtime: Runtime::new()?,
+            commands: Mutex::default(),
+        })
+    }
+}
+
+impl Plugin for NuopPlugin {
+    fn version(&self) -> String {
+        env!("CARGO_PKG_VERSION").to_string()
+    }
+
+    fn commands(&self) -> Vec<Box<dyn PluginCommand<Plugin = Self>>> {
+        signatures()
+            .into_iter()
+            .map(|signature| Box::new(K8sCommand(signature)) as Box<_>)
+            .collect()
+    }
+}
+
+/// A `k8s` command, run by [`Commands`] on the plugin's runtime.
+struct K8sCommand(Signature);
+
+impl SimplePluginCommand for K8sCommand {
+    type Plugin = NuopPlugin;
+
+    fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    fn signature(&self) -> Signature {
+        self.0.clone()
+    }
+
+    fn description(&self) -> &str {
+        &self.0.description
+    }
+
+    fn search_terms(&self) -> Vec<&str> {
+        vec!["kubernetes", "kubectl"]
+    }
+
+    fn run(
+        &self,
+        plugin: &NuopPlugin,
+        engine: &EngineInterface,
+        call: &EvaluatedCall,
+        input: &Value,
+    ) -> Result<Value, LabeledError> {
+        let dry_run = engine
+            .get_env_var(NUOP_DRY_RUN)?
+            .is_some_and(|v| v.as_str().is_ok_and(|v| v.eq_ignore_ascii_case("true")));
+        let (result, notices) = {
+            let mut commands = plugin.commands.lock().unwrap();
+            let result = plugin
+                .runtime
+                .block_on(commands.run(&self.0.name, call, input, dry_run));
+            (result, commands.take_notices())
+        };
+        // Printed with the engine's `print --stderr`, so they end up in the
+        // script's stderr, which the operator logs
+        if !notices.is_empty()
+            && let Some(print) = engine.find_decl("print")?
+        {
+            for notice in notices {
+                let print_call = EvaluatedCall::new(call.head)
+                    .with_positional(Value::string(notice, call.head))
+                    .with_flag("stderr".into_spanned(call.head));
+                engine.call_decl(print, print_call, PipelineData::empty(), false, false)?;
+            }
+        }
+        result
+            .map(|value| to_nu(&value, call.head))
+            .map_err(|e| LabeledError::new(e.clone()).with_label(e, call.head))
+    }
+}
+
+#[cfg(test)]
+mod commands_tests;
+
+#[cfg(test)]
+mod value_tests;
@@ -0,0 +1,60 @@
+//! Conversion between Nushell values and the JSON the Kubernetes API speaks.
+
+use nu_protocol::{Record, Span, Value};
+use serde_json::Map;
+
+/// Converts JSON to a Nushell value located at `span`.
+pub(crate) fn to_nu(value: &serde_json::Value, span: Span) -> Value {
+    match value {
+        serde_json::Value::Null => Value::nothing(span),
+        serde_json::Value::Bool(b) => Value::bool(*b, span),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(i) => Value::int(i, span),
+            None => Value::float(n.as_f64().unwrap_or_default(), span),
+        },
+        serde_json::Value::String(s) => Value::string(s, span),
+        serde_json::Value::Array(items) => {
+            Value::list(items.iter().map(|item| to_nu(item, span)).collect(), span)
+        }
+        serde_json::Value::Object(fields) => Value::record(
+            fields
+                .iter()
+                .map(|(k, v)| (k.clone(), to_nu(v, span)))
+                .collect::<Record>(),
+            span,
+        ),
+    }
+}
+
+/// Converts a Nushell value to JSON. Dates become RFC 3339 strings, file
+/// sizes bytes and durations nanoseconds.
+pub(crate) fn from_nu(value: &Value) -> Result<serde_json::Value, String> {
+    Ok(match value {
+        Value::Nothing { .. } => serde_json::Value::Null,
+        Value::Bool { val, .. } => (*val).into(),
+        Value::Int { val, .. } => (*val).into(),
+        Value::Float { val, .. } => serde_json::Number::from_f64(*val)
+            .ok_or_else(|| format!("{val} cannot be sent to Kubernetes"))?
+            .into(),
+        Value::String { val, .. } | Value::Glob { val, .. } => val.as_str().into(),
+        Value::Filesize { val, .. } => val.get().into(),
+        Value::Duration { val, .. } => (*val).into(),
+        Value::Date { val, .. } => val.to_rfc3339().into(),
+        Value::Record { val, .. } => val
+            .iter()
+            .map(|(k, v)| Ok((k.clone(), from_nu(v)?)))
+            .collect::<Result<Map<_, _>, String>>()?
+            .into(),
+        Value::List { vals, .. } => vals
+            .iter()
+            .map(from_nu)
+            .collect::<Result<Vec<_>, _>>()?
+            .into(),
+        other => {
+            return Err(format!(
+                "{} values cannot be sent to Kubernetes",
+                other.get_type()
+            ));
+        }
+    })
+}
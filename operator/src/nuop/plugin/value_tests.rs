@@ -0,0 +1,34 @@
+use nu_protocol::{Span, Value, engine::Closure};
+use serde_json::json;
+
+use super::value::{from_nu, to_nu};
+
+#[test]
+fn test_value_conversion_round_trips() {
+    let value = json!({
+        "metadata": { "name": "web", "labels": { "app": "web" } },
+        "spec": { "replicas": 3, "ratio": 0.5, "paused": false, "ports": [80, 443] },
+        "status": null
+    });
+
+    let nu = to_nu(&value, Span::test_data());
+    let spec = nu.get_data_by_key("spec").unwrap();
+    assert_eq!(spec.get_data_by_key("replicas"), Some(Value::test_int(3)));
+    assert_eq!(
+        spec.get_data_by_key("ports").unwrap().as_list().unwrap()[1],
+        Value::test_int(443)
+    );
+    assert_eq!(from_nu(&nu), Ok(value));
+}
+
+#[test]
+fn test_from_nu_rejects_closures() {
+    let closure = Value::test_closure(Closure {
+        block_id: nu_protocol::BlockId::new(0),
+        captures: vec![],
+    });
+    assert_eq!(
+        from_nu(&closure),
+        Err("closure values cannot be sent to Kubernetes".to_string())
+    );
+}
@@ -0,0 +1,222 @@
+use std::collections::HashSet;
+
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::OwnerReference;
+use kube::{
+    Api, Error, ResourceExt,
+    api::{DeleteParams, DynamicObject, GroupVersionKind, ListParams, Patch, PatchParams},
+    discovery::Scope,
+};
+use serde::Deserialize;
+use tracing::{debug, info};
+
+use crate::nuop::util::to_kube_error;
+
+use super::state::{CommandExecutor, State};
+
+pub const INVENTORY_LABEL: &str = "nuop.kemper.buzz/inventory";
+
+/// Parses the desired children printed by a declarative script. Accepts a
+/// single manifest, a list of manifests, or several YAML documents.
+pub(crate) fn parse_manifests(stdout: &str) -> Result<Vec<DynamicObject>, Error> {
+    let mut manifests = Vec::new();
+    for document in serde_yaml::Deserializer::from_str(stdout) {
+        let value = serde_json::Value::deserialize(document)
+            .map_err(|e| to_kube_error(&e.to_string(), "Failed to parse desired manifests", 500))?;
+        match value {
+            serde_json::Value::Null => {}
+            serde_json::Value::Array(items) => {
+                for item in items {
+                    manifests.push(to_manifest(item)?);
+                }
+            }
+            value => manifests.push(to_manifest(value)?),
+        }
+    }
+    Ok(manifests)
+}
+
+fn to_manifest(value: serde_json::Value) -> Result<DynamicObject, Error> {
+    serde_json::from_value::<DynamicObject>(value)
+        .map_err(|e| to_kube_error(&e.to_string(), "Invalid desired manifest", 500))
+        .and_then(|obj| {
+            if obj.types.is_none() || obj.metadata.name.is_none() {
+                Err(to_kube_error(
+                    "apiVersion, kind and metadata.name are required",
+                    "Invalid desired manifest",
+                    500,
+                ))
+            } else {
+                Ok(obj)
+            }
+        })
+}
+
+fn gvk_of(obj: &DynamicObject) -> GroupVersionKind {
+    let types = obj.types.clone().unwrap_or_default();
+    let (group, version) = match types.api_version.split_once('/') {
+        Some((group, version)) => (group.to_string(), version.to_string()),
+        None => (String::new(), types.api_version),
+    };
+    GroupVersionKind {
+        group,
+        version,
+        kind: types.kind,
+    }
+}
+
+fn owner_reference(owner: &DynamicObject) -> Option<OwnerReference> {
+    let types = owner.types.as_ref()?;
+    Some(OwnerReference {
+        api_version: types.api_version.clone(),
+        kind: types.kind.clone(),
+        name: owner.name_any(),
+        uid: owner.uid()?,
+        controller: Some(true),
+        block_owner_deletion: Some(true),
+    })
+}
+
+/// What [`apply_children`] changed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct ChildrenOutcome {
+    /// A child was created or modified
+    pub applied: bool,
+    /// A child that is no longer desired was deleted
+    pub pruned: bool,
+}
+
+impl ChildrenOutcome {
+    pub fn changed(&self) -> bool {
+        self.applied || self.pruned
+    }
+}
+
+/// Server-side applies the desired children of `owner` and prunes children
+/// from earlier runs that are no longer desired. Only kinds printed in this
+/// run or listed in `pruneKinds` are pruned, so nothing depends on state
+/// kept between runs.
+pub(crate) async fn apply_children<E>(
+    ctx: &State<E>,
+    owner: &DynamicObject,
+    desired: Vec<DynamicObject>,
+) -> Result<ChildrenOutcome, Error>
+where
+    E: CommandExecutor,
+{
+    let owner_uid = owner
+        .uid()
+        .ok_or_else(|| to_kube_error("Owner has no uid", "Failed to apply child resources", 500))?;
+    let owner_namespace = owner.namespace();
+    let field_manager = format!("nuop-{}", ctx.config.name);
+    let patch_params = PatchParams::apply(&field_manager).force();
+    let dry_run = ctx.config.dry_run;
+
+    let mut outcome = ChildrenOutcome::default();
+    let mut applied = HashSet::new();
+    for mut child in desired {
+        let gvk = gvk_of(&child);
+        let (api_resource, caps) = ctx.resolve_kind(&gvk).await?;
+
+        let namespace = match caps.scope {
+            Scope::Namespaced => child.namespace().or_else(|| owner_namespace.clone()),
+            Scope::Cluster => None,
+        };
+        child.metadata.namespace = namespace.clone();
+        child
+            .labels_mut()
+            .insert(INVENTORY_LABEL.to_string(), owner_uid.clone());
+
+        // Owner references cannot cross namespaces
+        if owner_namespace.is_none() || owner_namespace == namespace {
+            child.metadata.owner_references = owner_reference(owner).map(|o| vec![o]);
+        }
+
+        let name = child.name_any();
+        applied.insert((gvk.clone(), namespace.clone(), name.clone()));
+
+        if dry_run {
+            info!(
+                "Dry run: would apply {} {}/{} as {}: {}",
+                gvk.kind,
+                namespace.clone().unwrap_or_default(),
+                name,
+                field_manager,
+                serde_json::to_string(&child).unwrap_or_default()
+            );
+            outcome.applied = true;
+            continue;
+        }
+
+        let api: Api<DynamicObject> = match &namespace {
+            Some(ns) => Api::namespaced_with(ctx.client.clone(), ns, &api_resource),
+            None => Api::all_with(ctx.client.clone(), &api_resource),
+        };
+        // A no-op apply leaves the resourceVersion untouched
+        let before = api
+            .get_opt(&name)
+            .await
+            .map_err(|e| to_kube_error(&e.to_string(), "Failed to get child resource", 500))?
+            .and_then(|existing| existing.resource_version());
+        let after = api
+            .patch(&name, &patch_params, &Patch::Apply(&child))
+            .await
+            .map_err(|e| to_kube_error(&e.to_string(), "Failed to apply child resource", 500))?
+            .resource_version();
+        if before.is_none() || before != after {
+            outcome.applied = true;
+            debug!("Applied {} {}", gvk.kind, name);
+        }
+    }
+
+    let kinds: HashSet<GroupVersionKind> = applied
+        .iter()
+        .map(|(gvk, _, _)| gvk.clone())
+        .chain(ctx.config.prune_kinds.iter().cloned())
+        .collect();
+
+    let list_params = ListParams::default().labels(&format!("{INVENTORY_LABEL}={owner_uid}"));
+    for gvk in kinds {
+        let (api_resource, _) = ctx.resolve_kind(&gvk).await?;
+        let existing = Api::<DynamicObject>::all_with(ctx.client.clone(), &api_resource)
+            .list(&list_params)
+            .await
+            .map_err(|e| to_kube_error(&e.to_string(), "Failed to list child resources", 500))?;
+
+        for child in existing {
+            let namespace = child.namespace();
+            let name = child.name_any();
+            if applied.contains(&(gvk.clone(), namespace.clone(), name.clone())) {
+                continue;
+            }
+
+            outcome.pruned = true;
+            if dry_run {
+                info!(
+                    "Dry run: would prune {} {}/{}",
+                    gvk.kind,
+                    namespace.unwrap_or_default(),
+                    name
+                );
+                continue;
+            }
+
+            let api: Api<DynamicObject> = match &namespace {
+                Some(ns) => Api::namespaced_with(ctx.client.clone(), ns, &api_resource),
+                None => Api::all_with(ctx.client.clone(), &api_resource),
+            };
+            api.delete(&name, &DeleteParams::background())
+                .await
+                .map_err(|e| {
+                    to_kube_error(&e.to_string(), "Failed to prune child resource", 500)
+                })?;
+            info!(
+                "Pruned {} {}/{}",
+                gvk.kind,
+                namespace.unwrap_or_default(),
+                name
+            );
+        }
+    }
+
+    Ok(outcome)
+}
@@ -0,0 +1,342 @@
+use std::path::PathBuf;
+
+use http::{Method, Request, Response};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::ObjectMeta;
+use kube::{
+    Client,
+    api::{ApiResource, DynamicObject, GroupVersionKind},
+    client::Body,
+};
+use serde_json::{Value, json};
+use tempfile::TempDir;
+use tower_test::mock;
+
+use super::{
+    children::{ChildrenOutcome, INVENTORY_LABEL, apply_children, parse_manifests},
+    config::{Config, ReconcileMode},
+    state::{ProcessExecutor, State},
+};
+
+fn create_declarative_config() -> Config {
+    Config {
+        name: "test-declarative".to_string(),
+        group: "example.com".to_string(),
+        version: "v1".to_string(),
+        kind: "App".to_string(),
+        mode: ReconcileMode::Declarative,
+        ..Default::default()
+    }
+}
+
+/// A declarative script in its own directory, removed with the returned guard.
+fn create_script() -> (TempDir, PathBuf) {
+    let dir = tempfile::tempdir().unwrap();
+    let script = dir.path().join("mod.nu");
+    std::fs::write(&script, "def \"main reconcile\" [] {}").unwrap();
+    (dir, script)
+}
+
+fn create_owner() -> DynamicObject {
+    let mut owner = DynamicObject::new(
+        "my-app",
+        &ApiResource::from_gvk(&GroupVersionKind {
+            group: "example.com".to_string(),
+            version: "v1".to_string(),
+            kind: "App".to_string(),
+        }),
+    );
+    owner.metadata = ObjectMeta {
+        name: Some("my-app".to_string()),
+        namespace: Some("default".to_string()),
+        uid: Some("owner-uid".to_string()),
+        ..Default::default()
+    };
+    owner
+}
+
+fn json_response(body: Value) -> Response<Body> {
+    Response::builder()
+        .status(200)
+        .header("content-type", "application/json")
+        .body(Body::from(serde_json::to_vec(&body).unwrap()))
+        .unwrap()
+}
+
+fn not_found() -> Response<Body> {
+    let status = json!({
+        "kind": "Status",
+        "apiVersion": "v1",
+        "metadata": {},
+        "status": "Failure",
+        "reason": "NotFound",
+        "code": 404
+    });
+    Response::builder()
+        .status(404)
+        .header("content-type", "application/json")
+        .body(Body::from(serde_json::to_vec(&status).unwrap()))
+        .unwrap()
+}
+
+fn versioned(mut obj: Value, resource_version: &str) -> Value {
+    obj["metadata"]["resourceVersion"] = json!(resource_version);
+    obj
+}
+
+fn config_map(name: &str) -> Value {
+    json!({
+        "apiVersion": "v1",
+        "kind": "ConfigMap",
+        "metadata": {
+            "name": name,
+            "namespace": "default",
+            "labels": { INVENTORY_LABEL: "owner-uid" }
+        }
+    })
+}
+
+#[test]
+fn test_parse_manifests_formats() {
+    let single = "apiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: a\n";
+    assert_eq!(parse_manifests(single).unwrap().len(), 1);
+
+    let list = r#"[{"apiVersion":"v1","kind":"ConfigMap","metadata":{"name":"a"}},
+                   {"apiVersion":"v1","kind":"Secret","metadata":{"name":"b"}}]"#;
+    assert_eq!(parse_manifests(list).unwrap().len(), 2);
+
+    let multi_doc = "apiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: a\n---\n\
+                     apiVersion: v1\nkind: Secret\nmetadata:\n  name: b\n";
+    let manifests = parse_manifests(multi_doc).unwrap();
+    assert_eq!(manifests.len(), 2);
+    assert_eq!(manifests[1].types.as_ref().unwrap().kind, "Secret");
+
+    assert!(parse_manifests("").unwrap().is_empty());
+    assert!(parse_manifests("kind: ConfigMap\nmetadata:\n  name: a\n").is_err());
+    assert!(parse_manifests("apiVersion: v1\nkind: ConfigMap\n").is_err());
+}
+
+#[tokio::test]
+async fn test_apply_children_applies_and_prunes() {
+    let (mock_service, mut handle) = mock::pair::<Request<Body>, Response<Body>>();
+    let client = Client::new(mock_service, "default");
+    let (_dir, script) = create_script();
+    let ctx = State::new(
+        ApiResource::from_gvk(&GroupVersionKind {
+            group: "example.com".to_string(),
+            version: "v1".to_string(),
+            kind: "App".to_string(),
+        }),
+        client,
+        create_declarative_config(),
+        script,
+        ProcessExecutor::default(),
+    );
+
+    let server = tokio::spawn(async move {
+        let (request, send) = handle.next_request().await.expect("discovery not called");
+        assert_eq!(request.method(), Method::GET);
+        assert_eq!(request.uri().path(), "/api/v1");
+        send.send_response(json_response(json!({
+            "kind": "APIResourceList",
+            "groupVersion": "v1",
+            "resources": [{
+                "name": "configmaps",
+                "singularName": "configmap",
+                "namespaced": true,
+                "kind": "ConfigMap",
+                "verbs": ["get", "list", "watch", "create", "update", "patch", "delete"]
+            }]
+        })));
+
+        let (request, send) = handle.next_request().await.expect("get not called");
+        assert_eq!(request.method(), Method::GET);
+        assert_eq!(
+            request.uri().path(),
+            "/api/v1/namespaces/default/configmaps/keep"
+        );
+        send.send_response(json_response(versioned(config_map("keep"), "1")));
+
+        let (request, send) = handle.next_request().await.expect("apply not called");
+        assert_eq!(request.method(), Method::PATCH);
+        assert_eq!(
+            request.uri().path(),
+            "/api/v1/namespaces/default/configmaps/keep"
+        );
+        let query = request.uri().query().unwrap_or_default().to_string();
+        assert!(query.contains("fieldManager=nuop-test-declarative"));
+        assert!(query.contains("force=true"));
+        let body = request.into_body().collect_bytes().await.unwrap();
+        let applied: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(applied["metadata"]["labels"][INVENTORY_LABEL], "owner-uid");
+        assert_eq!(applied["metadata"]["namespace"], "default");
+        assert_eq!(
+            applied["metadata"]["ownerReferences"][0]["uid"],
+            "owner-uid"
+        );
+        send.send_response(json_response(versioned(config_map("keep"), "1")));
+
+        let (request, send) = handle.next_request().await.expect("list not called");
+        assert_eq!(request.method(), Method::GET);
+        assert_eq!(request.uri().path(), "/api/v1/configmaps");
+        assert!(
+            request
+                .uri()
+                .query()
+                .unwrap_or_default()
+                .contains("labelSelector=")
+        );
+        send.send_response(json_response(json!({
+            "apiVersion": "v1",
+            "kind": "ConfigMapList",
+            "metadata": {},
+            "items": [config_map("keep"), config_map("stale")]
+        })));
+
+        let (request, send) = handle.next_request().await.expect("delete not called");
+        assert_eq!(request.method(), Method::DELETE);
+        assert_eq!(
+            request.uri().path(),
+            "/api/v1/namespaces/default/configmaps/stale"
+        );
+        send.send_response(json_response(config_map("stale")));
+    });
+
+    let desired = parse_manifests("apiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: keep\n")
+        .expect("valid manifest");
+    let outcome = apply_children(&ctx, &create_owner(), desired)
+        .await
+        .expect("apply should succeed");
+
+    assert_eq!(
+        outcome,
+        ChildrenOutcome {
+            applied: false,
+            pruned: true
+        }
+    );
+    server.await.expect("mock server failed");
+}
+
+#[tokio::test]
+async fn test_apply_children_dry_run_only_reads() {
+    let (mock_service, mut handle) = mock::pair::<Request<Body>, Response<Body>>();
+    let client = Client::new(mock_service, "default");
+    let mut config = create_declarative_config();
+    config.dry_run = true;
+    let (_dir, script) = create_script();
+    let ctx = State::new(
+        ApiResource::from_gvk(&GroupVersionKind {
+            group: "example.com".to_string(),
+            version: "v1".to_string(),
+            kind: "App".to_string(),
+        }),
+        client,
+        config,
+        script,
+        ProcessExecutor::default(),
+    );
+
+    let server = tokio::spawn(async move {
+        let (_, send) = handle.next_request().await.expect("discovery not called");
+        send.send_response(json_response(json!({
+            "kind": "APIResourceList",
+            "groupVersion": "v1",
+            "resources": [{
+                "name": "configmaps",
+                "singularName": "configmap",
+                "namespaced": true,
+                "kind": "ConfigMap",
+                "verbs": ["list", "patch", "delete"]
+            }]
+        })));
+
+        let (request, send) = handle.next_request().await.expect("list not called");
+        assert_eq!(request.method(), Method::GET);
+        send.send_response(json_response(json!({
+            "apiVersion": "v1",
+            "kind": "ConfigMapList",
+            "metadata": {},
+            "items": [config_map("stale")]
+        })));
+    });
+
+    let desired = parse_manifests("apiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: keep\n")
+        .expect("valid manifest");
+    let outcome = apply_children(&ctx, &create_owner(), desired)
+        .await
+        .expect("dry run should succeed");
+
+    assert_eq!(
+        outcome,
+        ChildrenOutcome {
+            applied: true,
+            pruned: true
+        }
+    );
+    server.await.expect("mock server failed");
+}
+
+#[tokio::test]
+async fn test_apply_children_reports_created_children() {
+    let (mock_service, mut handle) = mock::pair::<Request<Body>, Response<Body>>();
+    let client = Client::new(mock_service, "default");
+    let (_dir, script) = create_script();
+    let ctx = State::new(
+        ApiResource::from_gvk(&GroupVersionKind {
+            group: "example.com".to_string(),
+            version: "v1".to_string(),
+            kind: "App".to_string(),
+        }),
+        client,
+        create_declarative_config(),
+        script,
+        ProcessExecutor::default(),
+    );
+
+    let server = tokio::spawn(async move {
+        let (_, send) = handle.next_request().await.expect("discovery not called");
+        send.send_response(json_response(json!({
+            "kind": "APIResourceList",
+            "groupVersion": "v1",
+            "resources": [{
+                "name": "configmaps",
+                "singularName": "configmap",
+                "namespaced": true,
+                "kind": "ConfigMap",
+                "verbs": ["get", "list", "patch", "delete"]
+            }]
+        })));
+
+        let (request, send) = handle.next_request().await.expect("get not called");
+        assert_eq!(request.method(), Method::GET);
+        send.send_response(not_found());
+
+        let (request, send) = handle.next_request().await.expect("apply not called");
+        assert_eq!(request.method(), Method::PATCH);
+        send.send_response(json_response(versioned(config_map("keep"), "1")));
+
+        let (request, send) = handle.next_request().await.expect("list not called");
+        assert_eq!(request.method(), Method::GET);
+        send.send_response(json_response(json!({
+            "apiVersion": "v1",
+            "kind": "ConfigMapList",
+            "metadata": {},
+            "items": [versioned(config_map("keep"), "1")]
+        })));
+    });
+
+    let desired = parse_manifests("apiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: keep\n")
+        .expect("valid manifest");
+    let outcome = apply_children(&ctx, &create_owner(), desired)
+        .await
+        .expect("apply should succeed");
+
+    assert_eq!(
+        outcome,
+        ChildrenOutcome {
+            applied: true,
+            pruned: false
+        }
+    );
+    server.await.expect("mock server failed");
+}
@@ -1,3 +1,4 @@
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::LabelSelector;
 use kube::api::GroupVersionKind;
 use serde::Deserialize;
 use std::collections::BTreeMap;
@@ -7,25 +8,168 @@ pub struct Config {
     pub name: String,
     #[serde(default)]
     pub group: String,
+    #[serde(default)]
     pub version: String,
+    #[serde(default)]
     pub kind: String,
 
+    /// Further kinds handled by the same script, see [`Config::watched`]
+    #[serde(default)]
+    pub watches: Vec<Watch>,
+
     #[serde(default, rename = "labelSelectors")]
     pub label_selectors: BTreeMap<String, String>,
 
+    /// Set-based selector combined with `labelSelectors`
+    #[serde(default, rename = "labelSelector")]
+    pub label_selector: Option<LabelSelector>,
+
     #[serde(default, rename = "fieldSelectors")]
     pub field_selectors: BTreeMap<String, String>,
 
     #[serde(default)]
     pub finalizer: Option<String>,
 
+    #[serde(default)]
+    pub finalizers: Vec<String>,
+
     #[serde(default)]
     pub namespace: Option<String>,
 
-    #[serde(default = "default_requeue_after_change")]
+    #[serde(
+        default = "default_requeue_after_change",
+        rename = "requeueAfterChange",
+        alias = "requeue_after_change"
+    )]
     pub requeue_after_change: u64,
-    #[serde(default = "default_requeue_after_noop")]
+    #[serde(
+        default = "default_requeue_after_noop",
+        rename = "requeueAfterNoop",
+        alias = "requeue_after_noop"
+    )]
     pub requeue_after_noop: u64,
+
+    #[serde(default)]
+    pub params: BTreeMap<String, String>,
+
+    /// Params sourced from Secrets, their values are masked in logs and
+    /// recordings
+    #[serde(skip)]
+    pub secret_params: Vec<String>,
+
+    #[serde(default, rename = "dryRun")]
+    pub dry_run: bool,
+
+    #[serde(default)]
+    pub mode: ReconcileMode,
+
+    #[serde(default, rename = "pruneKinds")]
+    pub prune_kinds: Vec<GroupVersionKind>,
+
+    #[serde(default)]
+    pub predicates: Vec<String>,
+
+    /// CEL expression an object must satisfy before the script runs
+    #[serde(default)]
+    pub filter: Option<String>,
+
+    #[serde(default, rename = "skipUnchanged")]
+    pub skip_unchanged: bool,
+
+    #[serde(default, rename = "resyncSeconds")]
+    pub resync_seconds: Option<u64>,
+
+    #[serde(default, rename = "onDelete")]
+    pub on_delete: bool,
+
+    #[serde(default, rename = "finalizeTimeoutSeconds")]
+    pub finalize_timeout: Option<u64>,
+
+    #[serde(default, rename = "finalizeMaxRetries")]
+    pub finalize_max_retries: Option<u32>,
+
+    #[serde(default, rename = "finalizePolicy")]
+    pub finalize_policy: FinalizePolicy,
+
+    /// Scripts with a higher priority are dispatched to and finalize first
+    /// when several scripts watch the same kind
+    #[serde(default)]
+    pub priority: i32,
+
+    /// Kinds cached by the operator and handed to every invocation
+    #[serde(default)]
+    pub lookups: Vec<Lookup>,
+}
+
+/// A kind the operator caches for the script, so related objects can be
+/// resolved without querying the API server.
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct Lookup {
+    /// Key of the objects in the lookups file, defaults to the kind
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub group: String,
+    pub version: String,
+    pub kind: String,
+
+    #[serde(default, rename = "labelSelectors")]
+    pub label_selectors: BTreeMap<String, String>,
+
+    #[serde(default, rename = "fieldSelectors")]
+    pub field_selectors: BTreeMap<String, String>,
+
+    /// Only hand over objects in the namespace of the reconciled object
+    #[serde(default, rename = "sameNamespace")]
+    pub same_namespace: bool,
+}
+
+/// A kind watched in addition to, or instead of, the config's own. Unset
+/// selectors and requeue intervals are taken from the config.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Watch {
+    #[serde(default)]
+    pub group: String,
+    pub version: String,
+    pub kind: String,
+
+    #[serde(default)]
+    pub label_selectors: BTreeMap<String, String>,
+
+    #[serde(default)]
+    pub label_selector: Option<LabelSelector>,
+
+    #[serde(default)]
+    pub field_selectors: BTreeMap<String, String>,
+
+    #[serde(default, alias = "requeue_after_change")]
+    pub requeue_after_change: Option<u64>,
+    #[serde(default, alias = "requeue_after_noop")]
+    pub requeue_after_noop: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReconcileMode {
+    /// The script performs its own changes and reports them via exit code
+    #[default]
+    Imperative,
+    /// The script prints the desired child manifests and the operator applies them
+    Declarative,
+    /// The script prints a modified copy of the object and the operator applies the difference
+    Mutate,
+}
+
+/// What happens once finalizing exceeds its deadline or retry limit.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum FinalizePolicy {
+    /// Keep the finalizer and retry until `finalize` succeeds
+    #[default]
+    Block,
+    /// Remove the finalizer anyway and record a Warning event
+    Force,
 }
 
 fn default_requeue_after_change() -> u64 {
@@ -36,6 +180,42 @@ fn default_requeue_after_noop() -> u64 {
     5 * 60
 }
 
+impl Default for Config {
+    /// An empty config with the same defaults as one deserialized from `{}`.
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            group: String::new(),
+            version: String::new(),
+            kind: String::new(),
+            watches: Vec::new(),
+            label_selectors: BTreeMap::new(),
+            label_selector: None,
+            field_selectors: BTreeMap::new(),
+            finalizer: None,
+            finalizers: Vec::new(),
+            namespace: None,
+            requeue_after_change: default_requeue_after_change(),
+            requeue_after_noop: default_requeue_after_noop(),
+            params: BTreeMap::new(),
+            secret_params: Vec::new(),
+            dry_run: false,
+            mode: ReconcileMode::default(),
+            prune_kinds: Vec::new(),
+            predicates: Vec::new(),
+            filter: None,
+            skip_unchanged: false,
+            resync_seconds: None,
+            on_delete: false,
+            finalize_timeout: None,
+            finalize_max_retries: None,
+            finalize_policy: FinalizePolicy::default(),
+            priority: 0,
+            lookups: Vec::new(),
+        }
+    }
+}
+
 impl From<&Config> for GroupVersionKind {
     fn from(config: &Config) -> Self {
         GroupVersionKind {
@@ -47,18 +227,93 @@ impl From<&Config> for GroupVersionKind {
 }
 
 impl Config {
-    pub fn label_selectors(&self) -> Option<String> {
-        if !self.label_selectors.is_empty() {
-            Some(
-                self.label_selectors
-                    .iter()
-                    .map(|(k, v)| format!("{k}={v}"))
-                    .collect::<Vec<_>>()
-                    .join(","),
-            )
-        } else {
-            None
+    /// One config per watched kind, each running its own controller: the
+    /// config's own kind, if set, followed by its `watches`.
+    pub fn watched(&self) -> Vec<Config> {
+        let single = Config {
+            watches: vec![],
+            ..self.clone()
+        };
+        let own = (!self.kind.is_empty() || self.watches.is_empty()).then(|| single.clone());
+
+        let watches = self.watches.iter().map(|watch| {
+            let mut config = single.clone();
+            config.group = watch.group.clone();
+            config.version = watch.version.clone();
+            config.kind = watch.kind.clone();
+            if !watch.label_selectors.is_empty() || watch.label_selector.is_some() {
+                config.label_selectors = watch.label_selectors.clone();
+                config.label_selector = watch.label_selector.clone();
+            }
+            if !watch.field_selectors.is_empty() {
+                config.field_selectors = watch.field_selectors.clone();
+            }
+            if let Some(rac) = watch.requeue_after_change {
+                config.requeue_after_change = rac;
+            }
+            if let Some(ran) = watch.requeue_after_noop {
+                config.requeue_after_noop = ran;
+            }
+            config
+        });
+
+        own.into_iter().chain(watches).collect()
+    }
+
+    /// All finalizers managed by this script, in the order they are finalized.
+    pub fn finalizers(&self) -> Vec<&str> {
+        let mut finalizers: Vec<&str> = Vec::new();
+        for finalizer in self.finalizer.iter().chain(&self.finalizers) {
+            if !finalizers.contains(&finalizer.as_str()) {
+                finalizers.push(finalizer);
+            }
         }
+        finalizers
+    }
+
+    pub fn label_selectors(&self) -> Option<String> {
+        render_label_selector(&self.label_selectors, self.label_selector.as_ref())
+    }
+
+    /// Evaluates the label selectors against an object's labels, for watches
+    /// shared with scripts using other selectors.
+    pub fn matches_labels(&self, labels: &BTreeMap<String, String>) -> bool {
+        let selector = self.label_selector.as_ref();
+        let match_labels = selector.and_then(|s| s.match_labels.as_ref());
+        let expressions = selector.and_then(|s| s.match_expressions.as_ref());
+
+        self.label_selectors
+            .iter()
+            .chain(match_labels.into_iter().flatten())
+            .all(|(k, v)| labels.get(k) == Some(v))
+            && expressions.into_iter().flatten().all(|expression| {
+                let value = labels.get(&expression.key);
+                let listed = |v: &String| expression.values.iter().flatten().any(|e| e == v);
+                match expression.operator.as_str() {
+                    "In" => value.is_some_and(listed),
+                    "NotIn" => !value.is_some_and(listed),
+                    "Exists" => value.is_some(),
+                    "DoesNotExist" => value.is_none(),
+                    _ => false,
+                }
+            })
+    }
+
+    /// Evaluates the field selectors against an object, treating missing
+    /// fields as empty like the API server does.
+    pub fn matches_fields(&self, obj: &serde_json::Value) -> bool {
+        self.field_selectors.iter().all(|(path, expected)| {
+            let actual = path
+                .split('.')
+                .try_fold(obj, |value, segment| value.get(segment))
+                .map(|value| match value {
+                    serde_json::Value::String(s) => s.clone(),
+                    serde_json::Value::Null => String::new(),
+                    other => other.to_string(),
+                })
+                .unwrap_or_default();
+            &actual == expected
+        })
     }
 
     pub fn field_selectors(&self) -> Option<String> {
@@ -76,10 +331,152 @@ impl Config {
     }
 }
 
+/// Renders equality selectors and a set-based `LabelSelector` into the
+/// selector string used by the watcher, e.g. `app=web,env in (prod,staging),!legacy`.
+pub fn render_label_selector(
+    labels: &BTreeMap<String, String>,
+    selector: Option<&LabelSelector>,
+) -> Option<String> {
+    let mut requirements: Vec<String> = labels.iter().map(|(k, v)| format!("{k}={v}")).collect();
+
+    if let Some(selector) = selector {
+        for (k, v) in selector.match_labels.iter().flatten() {
+            if labels.get(k) != Some(v) {
+                requirements.push(format!("{k}={v}"));
+            }
+        }
+        for expression in selector.match_expressions.iter().flatten() {
+            let key = &expression.key;
+            let values = expression.values.as_deref().unwrap_or_default().join(",");
+            requirements.push(match expression.operator.as_str() {
+                "In" => format!("{key} in ({values})"),
+                "NotIn" => format!("{key} notin ({values})"),
+                "Exists" => key.clone(),
+                "DoesNotExist" => format!("!{key}"),
+                other => format!("{key} {other} ({values})"),
+            });
+        }
+    }
+
+    if requirements.is_empty() {
+        None
+    } else {
+        Some(requirements.join(","))
+    }
+}
+
+/// Checks label selector keys, values and match expressions against the
+/// Kubernetes label syntax.
+pub(crate) fn label_selector_errors(
+    labels: &BTreeMap<String, String>,
+    selector: Option<&LabelSelector>,
+) -> Vec<String> {
+    let mut errors = Vec::new();
+    let match_labels = selector.and_then(|s| s.match_labels.as_ref());
+    for (key, value) in labels.iter().chain(match_labels.into_iter().flatten()) {
+        if !is_qualified_name(key) {
+            errors.push(format!("invalid label selector key '{key}'"));
+        }
+        if !is_label_value(value) {
+            errors.push(format!(
+                "invalid label selector value '{value}' for '{key}'"
+            ));
+        }
+    }
+
+    let expressions = selector.and_then(|s| s.match_expressions.as_ref());
+    for expression in expressions.into_iter().flatten() {
+        let key = &expression.key;
+        let values = expression.values.as_deref().unwrap_or_default();
+        if !is_qualified_name(key) {
+            errors.push(format!("invalid match expression key '{key}'"));
+        }
+        match expression.operator.as_str() {
+            "In" | "NotIn" if values.is_empty() => errors.push(format!(
+                "match expression '{key} {}' requires values",
+                expression.operator
+            )),
+            "In" | "NotIn" => errors.extend(
+                values
+                    .iter()
+                    .filter(|value| !is_label_value(value))
+                    .map(|value| format!("invalid match expression value '{value}' for '{key}'")),
+            ),
+            "Exists" | "DoesNotExist" if !values.is_empty() => errors.push(format!(
+                "match expression '{key} {}' must not have values",
+                expression.operator
+            )),
+            "Exists" | "DoesNotExist" => {}
+            other => errors.push(format!(
+                "unknown match expression operator '{other}' for '{key}', expected In, NotIn, Exists or DoesNotExist"
+            )),
+        }
+    }
+    errors
+}
+
+/// Selector errors of each lookup, prefixed with its kind.
+pub(crate) fn lookup_errors(lookups: &[Lookup]) -> Vec<String> {
+    lookups
+        .iter()
+        .flat_map(|lookup| {
+            label_selector_errors(&lookup.label_selectors, None)
+                .into_iter()
+                .chain(field_selector_errors(&lookup.field_selectors))
+                .map(|e| format!("lookup {}: {e}", lookup.kind))
+        })
+        .collect()
+}
+
+/// Field selectors only support dotted field paths as keys.
+pub(crate) fn field_selector_errors(selectors: &BTreeMap<String, String>) -> Vec<String> {
+    selectors
+        .keys()
+        .filter(|key| {
+            key.is_empty()
+                || key
+                    .split('.')
+                    .any(|part| part.is_empty() || !part.chars().all(|c| c.is_ascii_alphanumeric()))
+        })
+        .map(|key| format!("invalid field selector key '{key}'"))
+        .collect()
+}
+
+fn is_qualified_name(key: &str) -> bool {
+    let (prefix, name) = match key.split_once('/') {
+        Some((prefix, name)) => (Some(prefix), name),
+        None => (None, key),
+    };
+    let prefix_valid = prefix.is_none_or(|p| {
+        !p.is_empty()
+            && p.len() <= 253
+            && p.split('.').all(|label| {
+                !label.is_empty()
+                    && label.len() <= 63
+                    && label
+                        .chars()
+                        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+                    && !label.starts_with('-')
+                    && !label.ends_with('-')
+            })
+    });
+    prefix_valid && !name.is_empty() && is_label_value(name)
+}
+
+fn is_label_value(value: &str) -> bool {
+    value.is_empty()
+        || (value.len() <= 63
+            && value.starts_with(|c: char| c.is_ascii_alphanumeric())
+            && value.ends_with(|c: char| c.is_ascii_alphanumeric())
+            && value
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')))
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum ReconcilePhase<'a> {
     NeedsFinalizer,
     Active,
-    Finalizing,
+    Finalizing(&'a str),
     Noop(&'a str),
 }
@@ -0,0 +1,182 @@
+use super::config::{Config, label_selector_errors};
+
+fn config(yaml: &str) -> Config {
+    serde_yaml::from_str(yaml).unwrap()
+}
+
+#[test]
+fn test_label_selectors_render_match_expressions() {
+    let config = config(
+        r#"
+name: a
+version: v1
+kind: Pod
+labelSelectors: { app: web }
+labelSelector:
+  matchLabels: { app: web, team: core }
+  matchExpressions:
+    - { key: env, operator: In, values: [prod, staging] }
+    - { key: tier, operator: NotIn, values: [cache] }
+    - { key: managed, operator: Exists }
+    - { key: legacy, operator: DoesNotExist }
+"#,
+    );
+
+    assert_eq!(
+        config.label_selectors().as_deref(),
+        Some("app=web,team=core,env in (prod,staging),tier notin (cache),managed,!legacy")
+    );
+    assert!(
+        label_selector_errors(&config.label_selectors, config.label_selector.as_ref()).is_empty()
+    );
+}
+
+#[test]
+fn test_label_selectors_empty() {
+    assert_eq!(
+        config("{name: a, version: v1, kind: Pod}").label_selectors(),
+        None
+    );
+    assert_eq!(
+        config("{name: a, version: v1, kind: Pod, labelSelector: {}}").label_selectors(),
+        None
+    );
+}
+
+#[test]
+fn test_label_selector_errors_cover_match_labels_and_values() {
+    let config = config(
+        "{name: a, version: v1, kind: Pod, labelSelector: {matchLabels: {'bad key!': ok}, matchExpressions: [{key: env, operator: NotIn, values: ['-prod']}]}}",
+    );
+
+    assert_eq!(
+        label_selector_errors(&config.label_selectors, config.label_selector.as_ref()),
+        vec![
+            "invalid label selector key 'bad key!'",
+            "invalid match expression value '-prod' for 'env'",
+        ]
+    );
+}
+
+#[test]
+fn test_watched_expands_watches() {
+    let config = config(
+        r#"
+name: replicator
+version: v1
+kind: Secret
+labelSelectors: { replicate: "yes" }
+requeueAfterNoop: 60
+watches:
+  - { version: v1, kind: ConfigMap, requeueAfterNoop: 120 }
+  - { group: apps, version: v1, kind: Deployment, labelSelectors: { app: web } }
+"#,
+    );
+
+    let watched = config.watched();
+    let kinds: Vec<(&str, &str)> = watched
+        .iter()
+        .map(|c| (c.group.as_str(), c.kind.as_str()))
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![("", "Secret"), ("", "ConfigMap"), ("apps", "Deployment")]
+    );
+    assert!(
+        watched
+            .iter()
+            .all(|c| c.watches.is_empty() && c.name == "replicator")
+    );
+
+    assert_eq!(watched[1].requeue_after_noop, 120);
+    assert_eq!(
+        watched[1].label_selectors().as_deref(),
+        Some("replicate=yes")
+    );
+    assert_eq!(watched[2].requeue_after_noop, 60);
+    assert_eq!(watched[2].label_selectors().as_deref(), Some("app=web"));
+}
+
+#[test]
+fn test_requeue_keys_accept_snake_case() {
+    let config = config(
+        "{name: a, requeue_after_change: 5, requeue_after_noop: 50, watches: [{version: v1, kind: Secret, requeue_after_change: 7, requeueAfterNoop: 70}]}",
+    );
+
+    assert_eq!(config.requeue_after_change, 5);
+    assert_eq!(config.requeue_after_noop, 50);
+    assert_eq!(config.watches[0].requeue_after_change, Some(7));
+    assert_eq!(config.watches[0].requeue_after_noop, Some(70));
+}
+
+#[test]
+fn test_watched_without_own_kind() {
+    let config = config(
+        "{name: replicator, watches: [{version: v1, kind: Secret}, {version: v1, kind: ConfigMap}]}",
+    );
+
+    let kinds: Vec<String> = config.watched().into_iter().map(|c| c.kind).collect();
+    assert_eq!(kinds, vec!["Secret", "ConfigMap"]);
+}
+
+#[test]
+fn test_matches_labels_and_fields() {
+    let config = config(
+        r#"
+name: a
+version: v1
+kind: Pod
+labelSelectors: { app: web }
+labelSelector:
+  matchExpressions:
+    - { key: env, operator: In, values: [prod, staging] }
+    - { key: tier, operator: NotIn, values: [cache] }
+    - { key: legacy, operator: DoesNotExist }
+fieldSelectors:
+  metadata.namespace: default
+  spec.nodeName: ""
+"#,
+    );
+    let labels = |pairs: &[(&str, &str)]| -> std::collections::BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    };
+
+    assert!(config.matches_labels(&labels(&[("app", "web"), ("env", "prod")])));
+    assert!(config.matches_labels(&labels(&[
+        ("app", "web"),
+        ("env", "staging"),
+        ("tier", "db")
+    ])));
+    assert!(!config.matches_labels(&labels(&[("app", "web")])));
+    assert!(!config.matches_labels(&labels(&[("app", "api"), ("env", "prod")])));
+    assert!(!config.matches_labels(&labels(&[
+        ("app", "web"),
+        ("env", "prod"),
+        ("tier", "cache")
+    ])));
+    assert!(!config.matches_labels(&labels(&[("app", "web"), ("env", "prod"), ("legacy", "")])));
+
+    let pod = |namespace: &str, node: Option<&str>| {
+        serde_json::json!({
+            "metadata": { "namespace": namespace },
+            "spec": { "nodeName": node }
+        })
+    };
+    assert!(config.matches_fields(&pod("default", None)));
+    assert!(!config.matches_fields(&pod("kube-system", None)));
+    assert!(!config.matches_fields(&pod("default", Some("node-1"))));
+}
+
+#[test]
+fn test_default_matches_deserialized_defaults() {
+    let deserialized = config("name: a");
+    let default = Config {
+        name: "a".to_string(),
+        ..Default::default()
+    };
+
+    assert_eq!(format!("{default:?}"), format!("{deserialized:?}"));
+}
@@ -1,86 +1,322 @@
-use futures::StreamExt;
+use futures::{StreamExt, TryStreamExt, channel::mpsc::unbounded, future};
+use k8s_openapi::chrono::Utc;
 use kube::runtime::watcher::Config as WatcherConfig;
 use kube::{
     Api, Client, Error,
     api::{ApiResource, DynamicObject, ResourceExt},
-    runtime::{Controller, controller::Action},
+    runtime::{
+        Controller, WatchStreamExt,
+        controller::Action,
+        reflector::{ObjectRef, reflector, store::Writer},
+        watcher,
+    },
 };
-use std::path::PathBuf;
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
-use tracing::{debug, error, info, warn};
+use tokio::sync::mpsc;
+use tracing::{Instrument, Level, debug, error, info, info_span, warn};
 
-use crate::nuop::util::to_kube_error;
+use crate::nuop::{
+    config::get_dry_run,
+    metrics::{ScriptLabels, metrics},
+    util::to_kube_error,
+};
 
-use super::config::{Config, ReconcilePhase};
-use super::finalizer::{add_finalizer, detect_phase, remove_finalizer};
-use super::state::{CommandExecutor, State};
+use super::children::{apply_children, parse_manifests};
+use super::config::{Config, ReconcileMode, ReconcilePhase, label_selector_errors, lookup_errors};
+use super::dispatch::{ClientSide, Target, common};
+use super::filter::filter_errors;
+use super::finalizer::{
+    add_finalizer, detect_phase, finalize_failed, finalize_succeeded, remove_finalizer,
+};
+use super::fingerprint::{input_hash, is_unchanged, record_reconciled};
+use super::lookup::{lookup_store, render_lookups};
+use super::mutation::{apply_mutation, parse_mutated};
+use super::predicate::{PredicateCache, WatchPredicate, parse_predicates};
+use super::script_log::{log_output, reconcile_span};
+use super::state::{CommandExecutor, CommandResult, InvocationContext, State, attempt_key};
 
 pub async fn reconcile<E>(obj: Arc<DynamicObject>, ctx: Arc<State<E>>) -> Result<Action, Error>
+where
+    E: CommandExecutor,
+{
+    let namespace = obj.namespace();
+    let name = obj.name_any();
+    let key = attempt_key(&obj);
+    let attempt = ctx.next_attempt(&key, obj.metadata.deletion_timestamp.is_some());
+
+    let span = reconcile_span(&ctx.config, namespace.as_deref(), &name, attempt);
+    let result = reconcile_object(&obj, &ctx, attempt).instrument(span).await;
+
+    if result.is_ok() {
+        ctx.reset_attempts(&key);
+    }
+
+    result
+}
+
+async fn reconcile_object<E>(
+    obj: &DynamicObject,
+    ctx: &Arc<State<E>>,
+    attempt: u32,
+) -> Result<Action, Error>
 where
     E: CommandExecutor,
 {
     let namespace = obj.namespace().unwrap_or_default();
-    let finalizer = ctx.config.finalizer.as_deref();
+    let finalizers = ctx.config.finalizers();
     let api = Api::namespaced_with(ctx.client.clone(), &namespace, &ctx.api_resource);
 
-    let phase = detect_phase(&obj, finalizer);
+    let dry_run = ctx.config.dry_run;
+    let phase = detect_phase(obj, &finalizers);
+
+    // Finalizing always runs so objects that stopped matching are cleaned up
+    if !matches!(phase, ReconcilePhase::Finalizing(_)) && !passes_filter(ctx, obj) {
+        metrics()
+            .reconciles_filtered
+            .get_or_create(&ScriptLabels {
+                script: ctx.config.name.clone(),
+            })
+            .inc();
+        return Ok(Action::await_change());
+    }
 
     match phase {
-        ReconcilePhase::NeedsFinalizer => add_finalizer(&api, &obj, finalizer.unwrap()).await,
-        ReconcilePhase::Active => run_delegate(&obj, &ctx, "reconcile").await,
-        ReconcilePhase::Finalizing => {
-            run_delegate(&obj, &ctx, "finalize").await?;
-            remove_finalizer(&api, &obj, finalizer.unwrap()).await
+        ReconcilePhase::NeedsFinalizer => {
+            let action = add_finalizer(&api, obj, &finalizers, dry_run).await?;
+            if dry_run {
+                // The finalizer never lands, so carry on as if it had
+                run_delegate(&api, obj, ctx, "reconcile", attempt).await
+            } else {
+                Ok(action)
+            }
+        }
+        ReconcilePhase::Active => run_reconcile(&api, obj, ctx, "reconcile", attempt).await,
+        ReconcilePhase::Finalizing(_)
+            if obj
+                .finalizers()
+                .iter()
+                .any(|f| ctx.finalize_after.contains(f)) =>
+        {
+            debug!(
+                "Waiting for earlier finalizers before finalizing {}",
+                obj.name_any()
+            );
+            Ok(Action::await_change())
+        }
+        ReconcilePhase::Finalizing(finalizer) => {
+            let mut context =
+                InvocationContext::new(&ctx.config, obj, ctx.pod_namespace.clone(), attempt);
+            context.finalizer = Some(finalizer.to_string());
+            context.lookups = render_lookups(&ctx.lookups, obj).await;
+
+            match run_with_context(&api, obj, ctx, "finalize", &context).await {
+                Ok(_) => {
+                    finalize_succeeded(&ctx.config, obj);
+                    remove_finalizer(&api, obj, finalizer, dry_run).await
+                }
+                Err(e) => finalize_failed(&api, obj, ctx, finalizer, attempt, e).await,
+            }
         }
-        ReconcilePhase::Noop(cmd) => run_delegate(&obj, &ctx, cmd).await,
+        ReconcilePhase::Noop(cmd) => run_reconcile(&api, obj, ctx, cmd, attempt).await,
     }
 }
 
-async fn run_delegate<E>(
+fn passes_filter<E>(ctx: &State<E>, obj: &DynamicObject) -> bool
+where
+    E: CommandExecutor,
+{
+    let Some(filter) = &ctx.filter else {
+        return true;
+    };
+    match filter.matches(obj) {
+        Ok(matched) => matched,
+        Err(e) => {
+            debug!(
+                "Filter of {} rejected {}: {}",
+                ctx.config.name,
+                obj.name_any(),
+                e
+            );
+            false
+        }
+    }
+}
+
+/// Runs the script unless neither the object nor the script changed since the
+/// last successful run, when the script opted into skipping.
+async fn run_reconcile<E>(
+    api: &Api<DynamicObject>,
     obj: &DynamicObject,
     ctx: &Arc<State<E>>,
     command: &str,
+    attempt: u32,
 ) -> Result<Action, Error>
 where
     E: CommandExecutor,
 {
-    let input_data = serde_yaml::to_string(obj)
-        .map_err(|e| to_kube_error(&e.to_string(), "Failed to serialize object", 500))?;
+    let config = &ctx.config;
+    if !config.skip_unchanged {
+        return run_delegate(api, obj, ctx, command, attempt).await;
+    }
 
-    debug!("Input data: {:?}", input_data);
+    let hash = input_hash(obj, ctx.script_hash().await);
+    let now = Utc::now().timestamp();
+    if is_unchanged(obj, &config.name, &hash, config.resync_seconds, now) {
+        debug!("Skipping unchanged object: {}", obj.name_any());
+        metrics()
+            .reconciles_skipped
+            .get_or_create(&ScriptLabels {
+                script: config.name.clone(),
+            })
+            .inc();
+        return Ok(Action::requeue(Duration::from_secs(
+            config.requeue_after_noop,
+        )));
+    }
 
-    let result = ctx
-        .executor
-        .execute(&ctx.script, command, &input_data)
-        .await
-        .map_err(|e| to_kube_error(&e.to_string(), "Failed to execute script", 500))?;
+    let action = run_delegate(api, obj, ctx, command, attempt).await?;
+    if !config.dry_run
+        && let Err(e) = record_reconciled(api, obj, &config.name, &hash, now).await
+    {
+        warn!("Failed to record reconcile of {}: {:?}", obj.name_any(), e);
+    }
+    Ok(action)
+}
 
-    if !result.stderr.is_empty() {
-        for line in result.stderr.lines() {
-            error!("stderr: {}", line);
-        }
+async fn run_delegate<E>(
+    api: &Api<DynamicObject>,
+    obj: &DynamicObject,
+    ctx: &Arc<State<E>>,
+    command: &str,
+    attempt: u32,
+) -> Result<Action, Error>
+where
+    E: CommandExecutor,
+{
+    let mut context = InvocationContext::new(&ctx.config, obj, ctx.pod_namespace.clone(), attempt);
+    context.lookups = render_lookups(&ctx.lookups, obj).await;
+    run_with_context(api, obj, ctx, command, &context).await
+}
+
+async fn run_with_context<E>(
+    api: &Api<DynamicObject>,
+    obj: &DynamicObject,
+    ctx: &Arc<State<E>>,
+    command: &str,
+    context: &InvocationContext,
+) -> Result<Action, Error>
+where
+    E: CommandExecutor,
+{
+    if tracing::enabled!(tracing::Level::DEBUG) {
+        debug!("Input data: {}", ctx.redactor.redact_object(obj));
+    }
+    let result = execute_delegate(&ctx.executor, &ctx.script, obj, command, context).await?;
+    if let Some(recorder) = &ctx.recorder
+        && let Err(e) = recorder
+            .record(&ctx.script, command, obj, context, &result, &ctx.redactor)
+            .await
+    {
+        warn!("Failed to record invocation of {}: {}", ctx.config.name, e);
     }
+    // Outside imperative mode stdout carries manifests, not log lines
+    let mode = match command {
+        "reconcile" => ctx.config.mode,
+        _ => ReconcileMode::Imperative,
+    };
 
-    if !result.stdout.is_empty() {
-        for line in result.stdout.lines() {
-            info!("stdout: {}", line);
+    log_output(
+        "stderr",
+        &ctx.redactor.mask(&result.stderr),
+        Level::ERROR,
+        true,
+    );
+    let imperative = mode == ReconcileMode::Imperative;
+    log_output(
+        "stdout",
+        &ctx.redactor.redact_output(&result.stdout),
+        if imperative {
+            Level::INFO
+        } else {
+            Level::DEBUG
+        },
+        imperative,
+    );
+
+    let action = action_for_exit_code(&ctx.config, obj, result.exit_code)?;
+    let changed = match mode {
+        ReconcileMode::Imperative => return Ok(action),
+        ReconcileMode::Declarative => {
+            let desired = parse_manifests(&result.stdout)?;
+            if !apply_children(ctx, obj, desired).await?.changed() {
+                return Ok(action);
+            }
+            true
         }
-    }
+        ReconcileMode::Mutate => {
+            let mutated = parse_mutated(&result.stdout, obj)?;
+            apply_mutation(api, obj, &mutated, &ctx.config.name, ctx.config.dry_run).await?
+        }
+    };
+
+    let requeue_after = if changed {
+        ctx.config.requeue_after_change
+    } else {
+        ctx.config.requeue_after_noop
+    };
+    Ok(Action::requeue(Duration::from_secs(requeue_after)))
+}
+
+/// Serializes the object and hands it to the script, independent of any
+/// cluster connection.
+pub(crate) async fn execute_delegate<E>(
+    executor: &E,
+    script: &Path,
+    obj: &DynamicObject,
+    command: &str,
+    context: &InvocationContext,
+) -> Result<CommandResult, Error>
+where
+    E: CommandExecutor,
+{
+    let input_data = serde_yaml::to_string(obj)
+        .map_err(|e| to_kube_error(&e.to_string(), "Failed to serialize object", 500))?;
 
-    let code = result.exit_code as u16;
+    let span = info_span!(
+        "script",
+        script = %context.script_name,
+        command,
+        exit_code = tracing::field::Empty,
+    );
+    let result = executor
+        .execute(script, command, &input_data, context)
+        .instrument(span.clone())
+        .await
+        .map_err(|e| to_kube_error(&e.to_string(), "Failed to execute script", 500))?;
+    span.record("exit_code", result.exit_code);
+    Ok(result)
+}
+
+pub(crate) fn action_for_exit_code(
+    config: &Config,
+    obj: &DynamicObject,
+    exit_code: i32,
+) -> Result<Action, Error> {
+    let code = exit_code as u16;
 
     match code {
         0 => {
             info!("No changes detected for object: {}", obj.name_any());
             Ok(Action::requeue(Duration::from_secs(
-                ctx.config.requeue_after_noop,
+                config.requeue_after_noop,
             )))
         }
         2 => {
             info!("Changes detected for object: {}", obj.name_any());
             Ok(Action::requeue(Duration::from_secs(
-                ctx.config.requeue_after_change,
+                config.requeue_after_change,
             )))
         }
         _ => Err(to_kube_error(
@@ -91,6 +327,31 @@ where
     }
 }
 
+/// Runs the script's `deleted` command for every object removed from the
+/// cluster. Delivery is best effort: deletions missed while the operator was
+/// down are never reported.
+pub(crate) async fn handle_deleted<E>(
+    mut deleted: mpsc::UnboundedReceiver<DynamicObject>,
+    ctx: Arc<State<E>>,
+) where
+    E: CommandExecutor,
+{
+    while let Some(obj) = deleted.recv().await {
+        let namespace = obj.namespace().unwrap_or_default();
+        let api = Api::namespaced_with(ctx.client.clone(), &namespace, &ctx.api_resource);
+
+        let span = reconcile_span(&ctx.config, obj.namespace().as_deref(), &obj.name_any(), 1);
+        async {
+            info!("Object deleted: {}/{}", namespace, obj.name_any());
+            if let Err(e) = run_delegate(&api, &obj, &ctx, "deleted", 1).await {
+                warn!("Delete hook failed for {}: {:?}", obj.name_any(), e);
+            }
+        }
+        .instrument(span)
+        .await;
+    }
+}
+
 pub fn error_policy<E>(_obj: Arc<DynamicObject>, err: &Error, _ctx: Arc<State<E>>) -> Action
 where
     E: CommandExecutor,
@@ -99,35 +360,143 @@ where
     Action::requeue(std::time::Duration::from_secs(300))
 }
 
-pub async fn controller(client: Client, config: Config, script: PathBuf) {
-    let gvk = (&config).into();
+/// Checks what would otherwise fail for every object, before a script's
+/// controller starts.
+fn startup_errors(config: &Config) -> Result<Vec<WatchPredicate>, String> {
+    let predicates = parse_predicates(&config.predicates)?;
+    let errors: Vec<String> =
+        label_selector_errors(&config.label_selectors, config.label_selector.as_ref())
+            .into_iter()
+            .chain(filter_errors(config.filter.as_deref()))
+            .chain(lookup_errors(&config.lookups))
+            .collect();
+    if errors.is_empty() {
+        Ok(predicates)
+    } else {
+        Err(errors.join(", "))
+    }
+}
+
+/// Runs the scripts of one kind off a single watch and reflector, handing
+/// each object to every script whose selectors match, in priority order.
+pub async fn controller(client: Client, scripts: Vec<(PathBuf, Config)>) {
+    let mut accepted = Vec::new();
+    for (script, mut config) in scripts {
+        config.dry_run |= get_dry_run();
+        match startup_errors(&config) {
+            Ok(predicates) => accepted.push((script, config, predicates)),
+            Err(e) => error!("Not starting controller for {}: {}", config.name, e),
+        }
+    }
+    let Some((_, first, _)) = accepted.first() else {
+        return;
+    };
+
+    let gvk = first.into();
     let api_resource = ApiResource::from_gvk(&gvk);
     let obj_api: Api<DynamicObject> = Api::all_with(client.clone(), &api_resource);
 
-    info!(
-        "Starting controller for config: {:?} and script: {:?}",
-        &config, &script
-    );
-    let context = Arc::new(State::new_default(
-        api_resource.clone(),
-        client,
-        config,
-        script,
-    ));
-
+    let label_selector = common(accepted.iter().map(|(_, c, _)| c.label_selectors()));
+    let field_selector = common(accepted.iter().map(|(_, c, _)| c.field_selectors()));
+    let client_side = ClientSide {
+        labels: label_selector.is_none(),
+        fields: field_selector.is_none(),
+    };
     let watcher_config = WatcherConfig {
-        label_selector: context.config.label_selectors(),
-        field_selector: context.config.field_selectors(),
+        label_selector: label_selector.flatten(),
+        field_selector: field_selector.flatten(),
         ..WatcherConfig::default()
     };
 
-    Controller::new_with(obj_api, watcher_config, api_resource)
-        .run(reconcile, error_policy, context)
-        .for_each(|res| async move {
-            match res {
-                Ok(obj) => info!("Reconciliation successful: {:?}", obj),
-                Err(e) => warn!("Reconciliation failed: {:?}", e),
+    let writer = Writer::new(api_resource.clone());
+    let store = writer.as_reader();
+
+    let mut targets = Vec::new();
+    let mut controllers = Vec::new();
+    let mut finalize_after: Vec<String> = Vec::new();
+    for (script, config, predicates) in accepted {
+        info!(
+            "Starting controller for config: {:?} and script: {:?}",
+            &config, &script
+        );
+        let mut state = State::new_default(api_resource.clone(), client.clone(), config, script);
+        // Scripts with a lower priority finalize once the ones before are done
+        state.finalize_after = finalize_after.clone();
+        state.lookups = state
+            .config
+            .lookups
+            .iter()
+            .map(|lookup| (lookup.clone(), lookup_store(&client, lookup)))
+            .collect();
+        finalize_after.extend(state.config.finalizers().into_iter().map(String::from));
+        let state = Arc::new(state);
+
+        let deleted = (state.config.on_delete && state.config.finalizers().is_empty()).then(|| {
+            let (deleted_tx, deleted_rx) = mpsc::unbounded_channel();
+            tokio::spawn(handle_deleted(deleted_rx, state.clone()));
+            deleted_tx
+        });
+
+        let (objects_tx, objects_rx) = unbounded();
+        controllers.push(
+            Controller::for_stream_with(objects_rx, store.clone(), api_resource.clone())
+                .run(reconcile, error_policy, state.clone())
+                .for_each(|res| async move {
+                    match res {
+                        Ok(obj) => info!("Reconciliation successful: {:?}", obj),
+                        Err(e) => warn!("Reconciliation failed: {:?}", e),
+                    }
+                }),
+        );
+        targets.push(Target {
+            state,
+            predicates: Mutex::new(PredicateCache::new(predicates)),
+            objects: objects_tx,
+            deleted,
+        });
+    }
+    let targets = Arc::new(targets);
+
+    // Deletions are read off the watch before the reflector forgets the object
+    let events = watcher(obj_api, watcher_config)
+        .default_backoff()
+        .inspect_ok({
+            let store = store.clone();
+            let targets = targets.clone();
+            move |event| {
+                let watcher::Event::Delete(obj) = event else {
+                    return;
+                };
+                for target in targets.iter() {
+                    target.forget(obj);
+                    if let Some(deleted) = &target.deleted
+                        && target.selects(obj, client_side)
+                    {
+                        let last_known = store
+                            .get(&ObjectRef::from_obj_with(obj, api_resource.clone()))
+                            .map_or_else(|| obj.clone(), |cached| (*cached).clone());
+                        let _ = deleted.send(last_known);
+                    }
+                }
             }
-        })
-        .await;
+        });
+
+    // Only changes to a script's selected properties trigger its reconcile
+    let dispatch = reflector(writer, events)
+        .applied_objects()
+        .for_each(move |result| {
+            match result {
+                Ok(obj) => {
+                    for target in targets.iter() {
+                        if target.selects(&obj, client_side) && target.changed(&obj) {
+                            let _ = target.objects.unbounded_send(Ok(obj.clone()));
+                        }
+                    }
+                }
+                Err(e) => warn!("Watch of {} failed: {:?}", gvk.kind, e),
+            }
+            future::ready(())
+        });
+
+    future::join(dispatch, future::join_all(controllers)).await;
 }
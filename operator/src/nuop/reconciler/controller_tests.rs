@@ -12,13 +12,19 @@ use kube::{
     runtime::controller::Action,
 };
 use serde_json::json;
+use tokio::sync::mpsc;
 use tower_test::mock;
 
+use crate::nuop::{
+    metrics::metrics,
+    testing::{RecordingExecutor, StubExecutor},
+};
+
 use super::{
-    config::{Config, ReconcilePhase},
-    controller::{error_policy, reconcile},
-    finalizer::detect_phase,
-    state::State,
+    config::{Config, FinalizePolicy, ReconcilePhase},
+    controller::{error_policy, handle_deleted, reconcile},
+    finalizer::{detect_phase, finalize_expired},
+    state::{ProcessExecutor, State},
 };
 
 fn create_test_config() -> Config {
@@ -27,12 +33,9 @@ fn create_test_config() -> Config {
         group: "apps".to_string(),
         version: "v1".to_string(),
         kind: "Deployment".to_string(),
-        label_selectors: BTreeMap::new(),
-        field_selectors: BTreeMap::new(),
         finalizer: Some("test.example.com/finalizer".to_string()),
         namespace: Some("default".to_string()),
-        requeue_after_change: 10,
-        requeue_after_noop: 300,
+        ..Default::default()
     }
 }
 
@@ -129,7 +132,7 @@ async fn test_reconcile_needs_finalizer() {
 
     tokio::spawn(async move {
         let (request, send_response) = handle.next_request().await.expect("service not called");
-        assert_eq!(request.method(), "PUT");
+        assert_eq!(request.method(), "PATCH");
         assert!(
             request
                 .uri()
@@ -235,7 +238,7 @@ async fn test_reconcile_finalizing() {
 
     tokio::spawn(async move {
         let (request, send_response) = handle.next_request().await.expect("service not called");
-        assert_eq!(request.method(), "PUT");
+        assert_eq!(request.method(), "PATCH");
         assert!(
             request
                 .uri()
@@ -362,16 +365,16 @@ async fn test_detect_phase_combinations() {
 
     // No finalizer configured
     assert_eq!(
-        detect_phase(&obj_no_finalizer, None),
+        detect_phase(&obj_no_finalizer, &[]),
         ReconcilePhase::Noop("reconcile")
     );
     assert_eq!(
-        detect_phase(&obj_with_finalizer, None),
+        detect_phase(&obj_with_finalizer, &[]),
         ReconcilePhase::Noop("reconcile")
     );
 
     // With finalizer configured
-    let finalizer = Some("test.example.com/finalizer");
+    let finalizer = &["test.example.com/finalizer"];
     assert_eq!(
         detect_phase(&obj_no_finalizer, finalizer),
         ReconcilePhase::NeedsFinalizer
@@ -382,11 +385,11 @@ async fn test_detect_phase_combinations() {
     );
     assert_eq!(
         detect_phase(&obj_deleting_with_finalizer, finalizer),
-        ReconcilePhase::Finalizing
+        ReconcilePhase::Finalizing("test.example.com/finalizer")
     );
     assert_eq!(
         detect_phase(&obj_deleting_no_finalizer, finalizer),
-        ReconcilePhase::NeedsFinalizer
+        ReconcilePhase::Active
     );
 }
 
@@ -552,7 +555,7 @@ async fn test_comprehensive_reconcile_scenarios() {
 
         tokio::spawn(async move {
             let (request, send_response) = handle.next_request().await.expect("service not called");
-            assert_eq!(request.method(), "PUT");
+            assert_eq!(request.method(), "PATCH");
             assert!(
                 request
                     .uri()
@@ -807,3 +810,747 @@ async fn test_configuration_variations() {
         assert_eq!(result, Action::requeue(Duration::from_secs(600))); // Custom requeue_after_noop
     }
 }
+
+#[tokio::test]
+async fn test_invocation_context_passed_to_executor() {
+    let (mock_service, _handle) = mock::pair::<Request<Body>, Response<Body>>();
+    let client = Client::new(mock_service, "default");
+
+    let mut config = create_test_config();
+    config.finalizer = None;
+    let api_resource = ApiResource::from_gvk(&(&config).into());
+    let executor = RecordingExecutor::new(StubExecutor::default());
+    let state = Arc::new(State::new(
+        api_resource,
+        client,
+        config,
+        get_test_script_path("success-no-changes"),
+        executor.clone(),
+    ));
+
+    let obj = Arc::new(create_test_object(
+        "test-deployment",
+        "default",
+        false,
+        false,
+    ));
+
+    let result = reconcile(obj, state).await.unwrap();
+    assert_eq!(result, Action::requeue(Duration::from_secs(300)));
+
+    let invocations = executor.invocations();
+    assert_eq!(invocations.len(), 1);
+    let context = &invocations[0].context;
+    assert_eq!(invocations[0].command, "reconcile");
+    assert_eq!(context.script_name, "test-controller");
+    assert_eq!(context.gvk.kind, "Deployment");
+    assert_eq!(context.namespace.as_deref(), Some("default"));
+    assert_eq!(context.name, "test-deployment");
+    assert_eq!(context.uid.as_deref(), Some("test-uid-123"));
+    assert_eq!(context.attempt, 1);
+    assert!(!context.dry_run);
+
+    let env: BTreeMap<_, _> = context.env_vars().into_iter().collect();
+    assert_eq!(env["NUOP_SCRIPT_NAME"], "test-controller");
+    assert_eq!(env["NUOP_GROUP"], "apps");
+    assert_eq!(env["NUOP_VERSION"], "v1");
+    assert_eq!(env["NUOP_KIND"], "Deployment");
+    assert_eq!(env["NUOP_OBJECT_NAMESPACE"], "default");
+    assert_eq!(env["NUOP_OBJECT_NAME"], "test-deployment");
+    assert_eq!(env["NUOP_OBJECT_UID"], "test-uid-123");
+    assert_eq!(env["NUOP_ATTEMPT"], "1");
+    assert_eq!(env["NUOP_DRY_RUN"], "false");
+}
+
+#[test]
+fn test_env_allowlist_keeps_home_and_path() {
+    let env = ProcessExecutor::allowed_env(&["NUOP_TEST_UNSET_VARIABLE".to_string()]);
+    let keys: Vec<&str> = env.iter().map(|(k, _)| k.as_str()).collect();
+
+    assert!(keys.contains(&"PATH"));
+    assert!(keys.contains(&"HOME"));
+    assert!(!keys.contains(&"NUOP_TEST_UNSET_VARIABLE"));
+}
+
+#[tokio::test]
+async fn test_attempt_count_resets_after_success() {
+    let (mock_service, _handle) = mock::pair::<Request<Body>, Response<Body>>();
+    let client = Client::new(mock_service, "default");
+
+    let mut config = create_test_config();
+    config.finalizer = None;
+    let api_resource = ApiResource::from_gvk(&(&config).into());
+    let failing = RecordingExecutor::new(StubExecutor::exit_code(1));
+    let state = Arc::new(State::new(
+        api_resource.clone(),
+        client.clone(),
+        config.clone(),
+        get_test_script_path("error"),
+        failing.clone(),
+    ));
+
+    let obj = Arc::new(create_test_object(
+        "test-deployment",
+        "default",
+        false,
+        false,
+    ));
+
+    assert!(reconcile(obj.clone(), state.clone()).await.is_err());
+    assert!(reconcile(obj.clone(), state.clone()).await.is_err());
+
+    let attempts: Vec<u32> = failing
+        .invocations()
+        .iter()
+        .map(|invocation| invocation.context.attempt)
+        .collect();
+    assert_eq!(attempts, vec![1, 2]);
+
+    assert_eq!(state.next_attempt("default/test-deployment", false), 3);
+    state.reset_attempts("default/test-deployment");
+    assert_eq!(state.next_attempt("default/test-deployment", false), 1);
+}
+
+#[tokio::test]
+async fn test_dry_run_skips_finalizer_writes() {
+    // The mock handle is dropped, so any API request would fail the reconcile
+    let (mock_service, _) = mock::pair::<Request<Body>, Response<Body>>();
+    let client = Client::new(mock_service, "default");
+
+    let mut config = create_test_config();
+    config.dry_run = true;
+    let api_resource = ApiResource::from_gvk(&(&config).into());
+    let executor = RecordingExecutor::new(StubExecutor::exit_code(2));
+    let state = Arc::new(State::new(
+        api_resource,
+        client,
+        config,
+        get_test_script_path("success-with-changes"),
+        executor.clone(),
+    ));
+
+    // Missing finalizer is only logged and the script still reconciles
+    let obj = Arc::new(create_test_object(
+        "test-deployment",
+        "default",
+        false,
+        false,
+    ));
+    let result = reconcile(obj, state.clone()).await.unwrap();
+    assert_eq!(result, Action::requeue(Duration::from_secs(10)));
+
+    // Finalizer removal is only logged after the script finalizes
+    let obj = Arc::new(create_test_object("test-deployment", "default", true, true));
+    let result = reconcile(obj, state).await.unwrap();
+    assert_eq!(result, Action::await_change());
+
+    executor.assert_commands(&["reconcile", "finalize"]);
+    assert!(
+        executor
+            .invocations()
+            .iter()
+            .all(|invocation| invocation.context.dry_run)
+    );
+}
+
+#[tokio::test]
+async fn test_deleted_objects_invoke_delete_hook() {
+    let (mock_service, _) = mock::pair::<Request<Body>, Response<Body>>();
+    let client = Client::new(mock_service, "default");
+
+    let mut config = create_test_config();
+    config.finalizer = None;
+    config.on_delete = true;
+    let api_resource = ApiResource::from_gvk(&(&config).into());
+    let executor = RecordingExecutor::new(StubExecutor::default());
+    let state = Arc::new(State::new(
+        api_resource,
+        client,
+        config,
+        get_test_script_path("success-no-changes"),
+        executor.clone(),
+    ));
+
+    let (deleted_tx, deleted_rx) = mpsc::unbounded_channel();
+    deleted_tx
+        .send(create_test_object("first", "default", false, false))
+        .unwrap();
+    deleted_tx
+        .send(create_test_object("second", "other", false, false))
+        .unwrap();
+    drop(deleted_tx);
+
+    handle_deleted(deleted_rx, state).await;
+
+    let invocations = executor.invocations();
+    let deleted: Vec<(&str, &str, Option<&str>)> = invocations
+        .iter()
+        .map(|invocation| {
+            (
+                invocation.command.as_str(),
+                invocation.context.name.as_str(),
+                invocation.context.namespace.as_deref(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        deleted,
+        vec![
+            ("deleted", "first", Some("default")),
+            ("deleted", "second", Some("other")),
+        ]
+    );
+}
+
+#[test]
+fn test_finalize_expired_by_timeout_and_retries() {
+    let mut config = create_test_config();
+    let obj = create_test_object("test-deployment", "default", true, true);
+    let deleted_at = obj.metadata.deletion_timestamp.clone().unwrap().0;
+
+    // Without limits finalizing never expires
+    assert!(!finalize_expired(
+        &config,
+        &obj,
+        100,
+        deleted_at + chrono::Duration::days(1)
+    ));
+
+    config.finalize_timeout = Some(60);
+    assert!(!finalize_expired(
+        &config,
+        &obj,
+        1,
+        deleted_at + chrono::Duration::seconds(59)
+    ));
+    assert!(finalize_expired(
+        &config,
+        &obj,
+        1,
+        deleted_at + chrono::Duration::seconds(60)
+    ));
+
+    config.finalize_timeout = None;
+    config.finalize_max_retries = Some(3);
+    assert!(!finalize_expired(&config, &obj, 3, deleted_at));
+    assert!(finalize_expired(&config, &obj, 4, deleted_at));
+}
+
+#[tokio::test]
+async fn test_finalize_block_policy_keeps_finalizer() {
+    // The mock handle is dropped, so removing the finalizer would fail differently
+    let (mock_service, _) = mock::pair::<Request<Body>, Response<Body>>();
+    let client = Client::new(mock_service, "default");
+
+    let mut config = create_test_config();
+    config.name = "finalize-block".to_string();
+    config.finalize_max_retries = Some(0);
+    let api_resource = ApiResource::from_gvk(&(&config).into());
+    let state = Arc::new(State::new(
+        api_resource,
+        client,
+        config,
+        get_test_script_path("error"),
+        RecordingExecutor::new(StubExecutor::exit_code(1)),
+    ));
+
+    let obj = Arc::new(create_test_object("test-deployment", "default", true, true));
+    let err = reconcile(obj, state).await.unwrap_err();
+    assert!(err.to_string().contains("Script exited with error"));
+
+    let encoded = metrics().encode();
+    assert!(encoded.contains(
+        r#"nuop_finalize_pending{script="finalize-block",namespace="default",name="test-deployment"} 1"#
+    ));
+}
+
+#[tokio::test]
+async fn test_finalize_force_policy_removes_finalizer() {
+    let (mock_service, mut handle) = mock::pair::<Request<Body>, Response<Body>>();
+    let client = Client::new(mock_service, "default");
+
+    let mut config = create_test_config();
+    config.name = "finalize-force".to_string();
+    config.finalize_max_retries = Some(1);
+    config.finalize_policy = FinalizePolicy::Force;
+    let api_resource = ApiResource::from_gvk(&(&config).into());
+    let state = Arc::new(State::new(
+        api_resource,
+        client,
+        config,
+        get_test_script_path("error"),
+        RecordingExecutor::new(StubExecutor::exit_code(1)),
+    ));
+
+    let obj = Arc::new(create_test_object("test-deployment", "default", true, true));
+
+    // The first failure is within the retry limit
+    assert!(reconcile(obj.clone(), state.clone()).await.is_err());
+
+    let server = tokio::spawn(async move {
+        let (request, send) = handle.next_request().await.expect("patch not called");
+        assert_eq!(request.method(), http::Method::PATCH);
+        let body = request.into_body().collect_bytes().await.unwrap();
+        let patch: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(patch[1]["value"], json!([]));
+        send.send_response(
+            Response::builder()
+                .status(StatusCode::OK)
+                .body(Body::from(
+                    serde_json::to_vec(&create_test_object(
+                        "test-deployment",
+                        "default",
+                        false,
+                        true,
+                    ))
+                    .unwrap(),
+                ))
+                .unwrap(),
+        );
+
+        let (request, send) = handle.next_request().await.expect("event not published");
+        assert_eq!(request.method(), http::Method::POST);
+        assert!(request.uri().path().ends_with("/events"));
+        let body = request.into_body().collect_bytes().await.unwrap();
+        let event: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(event["type"], "Warning");
+        assert_eq!(event["reason"], "FinalizerForceRemoved");
+        send.send_response(
+            Response::builder()
+                .status(StatusCode::CREATED)
+                .body(Body::from(body.to_vec()))
+                .unwrap(),
+        );
+    });
+
+    let result = reconcile(obj, state).await.unwrap();
+    assert_eq!(result, Action::await_change());
+    server.await.expect("mock server failed");
+
+    let encoded = metrics().encode();
+    assert!(encoded.contains(r#"nuop_finalizers_forced_total{script="finalize-force"} 1"#));
+    assert!(!encoded.contains(r#"nuop_finalize_pending{script="finalize-force""#));
+}
+
+#[tokio::test]
+async fn test_finalize_retries_ignore_failures_before_deletion() {
+    // The mock handle is dropped, so a forced removal would fail to patch
+    let (mock_service, _) = mock::pair::<Request<Body>, Response<Body>>();
+    let client = Client::new(mock_service, "default");
+
+    let mut config = create_test_config();
+    config.name = "finalize-after-failures".to_string();
+    config.finalize_max_retries = Some(1);
+    config.finalize_policy = FinalizePolicy::Force;
+    let api_resource = ApiResource::from_gvk(&(&config).into());
+    let failing = RecordingExecutor::new(StubExecutor::exit_code(1));
+    let state = Arc::new(State::new(
+        api_resource,
+        client,
+        config,
+        get_test_script_path("error"),
+        failing.clone(),
+    ));
+
+    let live = Arc::new(create_test_object(
+        "test-deployment",
+        "default",
+        true,
+        false,
+    ));
+    assert!(reconcile(live.clone(), state.clone()).await.is_err());
+    assert!(reconcile(live, state.clone()).await.is_err());
+
+    let deleting = Arc::new(create_test_object("test-deployment", "default", true, true));
+    assert!(reconcile(deleting, state).await.is_err());
+
+    let invocations: Vec<(String, u32)> = failing
+        .invocations()
+        .into_iter()
+        .map(|invocation| (invocation.command, invocation.context.attempt))
+        .collect();
+    assert_eq!(
+        invocations,
+        vec![
+            ("reconcile".to_string(), 1),
+            ("reconcile".to_string(), 2),
+            ("finalize".to_string(), 1),
+        ]
+    );
+    assert!(
+        !metrics()
+            .encode()
+            .contains(r#"nuop_finalizers_forced_total{script="finalize-after-failures"}"#)
+    );
+}
+
+#[test]
+fn test_detect_phase_multiple_finalizers() {
+    let finalizers = &["first.example.com/cleanup", "second.example.com/cleanup"];
+    let mut obj = create_test_object("test", "default", false, false);
+
+    obj.metadata.finalizers = Some(vec!["first.example.com/cleanup".to_string()]);
+    assert_eq!(
+        detect_phase(&obj, finalizers),
+        ReconcilePhase::NeedsFinalizer
+    );
+
+    obj.metadata.finalizers = Some(vec![
+        "other.example.com/keep".to_string(),
+        "second.example.com/cleanup".to_string(),
+        "first.example.com/cleanup".to_string(),
+    ]);
+    assert_eq!(detect_phase(&obj, finalizers), ReconcilePhase::Active);
+
+    // Finalizers are processed in configured order, one at a time
+    obj.metadata.deletion_timestamp = Some(Time(chrono::Utc::now()));
+    assert_eq!(
+        detect_phase(&obj, finalizers),
+        ReconcilePhase::Finalizing("first.example.com/cleanup")
+    );
+    obj.metadata.finalizers = Some(vec!["second.example.com/cleanup".to_string()]);
+    assert_eq!(
+        detect_phase(&obj, finalizers),
+        ReconcilePhase::Finalizing("second.example.com/cleanup")
+    );
+}
+
+#[tokio::test]
+async fn test_finalizer_patch_retries_on_conflict() {
+    let (mock_service, mut handle) = mock::pair::<Request<Body>, Response<Body>>();
+    let client = Client::new(mock_service, "default");
+
+    let mut config = create_test_config();
+    config.finalizers = vec!["second.example.com/cleanup".to_string()];
+    let api_resource = ApiResource::from_gvk(&(&config).into());
+    let state = Arc::new(State::new(
+        api_resource,
+        client,
+        config,
+        get_test_script_path("success-no-changes"),
+        RecordingExecutor::new(StubExecutor::default()),
+    ));
+
+    let obj = Arc::new(create_test_object(
+        "test-deployment",
+        "default",
+        false,
+        false,
+    ));
+
+    let server = tokio::spawn(async move {
+        // The cached object is stale
+        let (request, send) = handle.next_request().await.expect("patch not called");
+        assert_eq!(request.method(), http::Method::PATCH);
+        assert_eq!(
+            request.headers()["content-type"],
+            "application/json-patch+json"
+        );
+        let body = request.into_body().collect_bytes().await.unwrap();
+        let patch: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            patch[0],
+            json!({"op": "test", "path": "/metadata/resourceVersion", "value": "123"})
+        );
+        send.send_response(
+            Response::builder()
+                .status(StatusCode::UNPROCESSABLE_ENTITY)
+                .body(Body::from(
+                    serde_json::to_vec(&json!({
+                        "kind": "Status",
+                        "apiVersion": "v1",
+                        "metadata": {},
+                        "status": "Failure",
+                        "message": "the server rejected our request due to an error in our request",
+                        "reason": "Invalid",
+                        "code": 422
+                    }))
+                    .unwrap(),
+                ))
+                .unwrap(),
+        );
+
+        // A concurrent writer added its own finalizer in the meantime
+        let (request, send) = handle.next_request().await.expect("get not called");
+        assert_eq!(request.method(), http::Method::GET);
+        let mut fresh = create_test_object("test-deployment", "default", false, false);
+        fresh.metadata.resource_version = Some("124".to_string());
+        fresh.metadata.finalizers = Some(vec!["other.example.com/keep".to_string()]);
+        send.send_response(
+            Response::builder()
+                .status(StatusCode::OK)
+                .body(Body::from(serde_json::to_vec(&fresh).unwrap()))
+                .unwrap(),
+        );
+
+        let (request, send) = handle.next_request().await.expect("retry not called");
+        let body = request.into_body().collect_bytes().await.unwrap();
+        let patch: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(patch[0]["value"], "124");
+        assert_eq!(
+            patch[1]["value"],
+            json!([
+                "other.example.com/keep",
+                "test.example.com/finalizer",
+                "second.example.com/cleanup"
+            ])
+        );
+        send.send_response(
+            Response::builder()
+                .status(StatusCode::OK)
+                .body(Body::from(serde_json::to_vec(&fresh).unwrap()))
+                .unwrap(),
+        );
+    });
+
+    let result = reconcile(obj, state).await.unwrap();
+    assert_eq!(result, Action::requeue(Duration::from_secs(5)));
+    server.await.expect("mock server failed");
+}
+
+#[tokio::test]
+async fn test_finalizer_patch_returns_validation_errors() {
+    let (mock_service, mut handle) = mock::pair::<Request<Body>, Response<Body>>();
+    let client = Client::new(mock_service, "default");
+
+    let config = create_test_config();
+    let api_resource = ApiResource::from_gvk(&(&config).into());
+    let executor = RecordingExecutor::new(StubExecutor::default());
+    let state = Arc::new(State::new(
+        api_resource,
+        client,
+        config,
+        get_test_script_path("success-no-changes"),
+        executor.clone(),
+    ));
+
+    let obj = Arc::new(create_test_object(
+        "test-deployment",
+        "default",
+        false,
+        false,
+    ));
+
+    let server = tokio::spawn(async move {
+        let (request, send) = handle.next_request().await.expect("patch not called");
+        assert_eq!(request.method(), http::Method::PATCH);
+        send.send_response(
+            Response::builder()
+                .status(StatusCode::UNPROCESSABLE_ENTITY)
+                .body(Body::from(
+                    serde_json::to_vec(&json!({
+                        "kind": "Status",
+                        "apiVersion": "v1",
+                        "metadata": {},
+                        "status": "Failure",
+                        "message": "Deployment.apps \"test-deployment\" is invalid: metadata.finalizers: Forbidden: no new finalizers can be added if the object is being deleted",
+                        "reason": "Invalid",
+                        "code": 422
+                    }))
+                    .unwrap(),
+                ))
+                .unwrap(),
+        );
+        assert!(handle.next_request().await.is_none(), "patch retried");
+    });
+
+    let err = reconcile(obj, state).await.unwrap_err();
+    assert!(err.to_string().contains("no new finalizers"));
+    assert!(executor.invocations().is_empty());
+    server.await.expect("mock server failed");
+}
+
+#[tokio::test]
+async fn test_finalize_passes_pending_finalizer() {
+    let (mock_service, mut handle) = mock::pair::<Request<Body>, Response<Body>>();
+    let client = Client::new(mock_service, "default");
+
+    let mut config = create_test_config();
+    config.finalizers = vec!["second.example.com/cleanup".to_string()];
+    let api_resource = ApiResource::from_gvk(&(&config).into());
+    let executor = RecordingExecutor::new(StubExecutor::default());
+    let state = Arc::new(State::new(
+        api_resource,
+        client,
+        config,
+        get_test_script_path("success-no-changes"),
+        executor.clone(),
+    ));
+
+    let mut obj = create_test_object("test-deployment", "default", false, true);
+    obj.metadata.finalizers = Some(vec![
+        "second.example.com/cleanup".to_string(),
+        "other.example.com/keep".to_string(),
+    ]);
+
+    let server = tokio::spawn(async move {
+        let (request, send) = handle.next_request().await.expect("patch not called");
+        let body = request.into_body().collect_bytes().await.unwrap();
+        let patch: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(patch[1]["value"], json!(["other.example.com/keep"]));
+        send.send_response(
+            Response::builder()
+                .status(StatusCode::OK)
+                .body(Body::from(
+                    serde_json::to_vec(&create_test_object(
+                        "test-deployment",
+                        "default",
+                        false,
+                        true,
+                    ))
+                    .unwrap(),
+                ))
+                .unwrap(),
+        );
+    });
+
+    let result = reconcile(Arc::new(obj), state).await.unwrap();
+    assert_eq!(result, Action::await_change());
+    server.await.expect("mock server failed");
+
+    executor.assert_commands(&["finalize"]);
+    let context = &executor.invocations()[0].context;
+    assert_eq!(
+        context.finalizer.as_deref(),
+        Some("second.example.com/cleanup")
+    );
+    let env: BTreeMap<String, String> = context.env_vars().into_iter().collect();
+    assert_eq!(env["NUOP_FINALIZER"], "second.example.com/cleanup");
+}
+
+#[tokio::test]
+async fn test_skip_unchanged_runs_once_per_input() {
+    let (mock_service, mut handle) = mock::pair::<Request<Body>, Response<Body>>();
+    let client = Client::new(mock_service, "default");
+
+    let mut config = create_test_config();
+    config.finalizer = None;
+    config.skip_unchanged = true;
+    let api_resource = ApiResource::from_gvk(&(&config).into());
+    let executor = RecordingExecutor::new(StubExecutor::default());
+    let state = Arc::new(State::new(
+        api_resource,
+        client,
+        config,
+        get_test_script_path("success-no-changes"),
+        executor.clone(),
+    ));
+
+    let obj = create_test_object("test-deployment", "default", false, false);
+
+    let server = tokio::spawn(async move {
+        let (request, send) = handle.next_request().await.expect("record not called");
+        assert_eq!(request.method(), http::Method::PATCH);
+        assert_eq!(
+            request.headers()["content-type"],
+            "application/merge-patch+json"
+        );
+        let body = request.into_body().collect_bytes().await.unwrap();
+        let patch: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        let recorded =
+            patch["metadata"]["annotations"]["reconciled.nuop.kemper.buzz/test-controller"]
+                .as_str()
+                .unwrap()
+                .to_string();
+        send.send_response(
+            Response::builder()
+                .status(StatusCode::OK)
+                .body(Body::from(
+                    serde_json::to_vec(&create_test_object(
+                        "test-deployment",
+                        "default",
+                        false,
+                        false,
+                    ))
+                    .unwrap(),
+                ))
+                .unwrap(),
+        );
+        recorded
+    });
+
+    reconcile(Arc::new(obj.clone()), state.clone())
+        .await
+        .unwrap();
+    let recorded = server.await.expect("mock server failed");
+
+    // The recorded annotation suppresses the next run for the same input
+    let mut annotated = obj.clone();
+    annotated.metadata.annotations = Some(
+        [(
+            "reconciled.nuop.kemper.buzz/test-controller".to_string(),
+            recorded,
+        )]
+        .into_iter()
+        .collect(),
+    );
+    let result = reconcile(Arc::new(annotated), state).await.unwrap();
+    assert_eq!(result, Action::requeue(Duration::from_secs(300)));
+
+    assert_eq!(executor.invocations().len(), 1);
+}
+
+#[tokio::test]
+async fn test_filtered_objects_skip_the_script() {
+    let (mock_service, _handle) = mock::pair::<Request<Body>, Response<Body>>();
+    let client = Client::new(mock_service, "default");
+
+    let mut config = create_test_config();
+    config.name = "test-filtered".to_string();
+    config.filter = Some("object.spec.replicas > 3".to_string());
+    let api_resource = ApiResource::from_gvk(&(&config).into());
+    let executor = RecordingExecutor::new(StubExecutor::default());
+    let state = Arc::new(State::new(
+        api_resource,
+        client,
+        config,
+        get_test_script_path("success-no-changes"),
+        executor.clone(),
+    ));
+
+    // Neither the finalizer is added nor the script started
+    let obj = create_test_object("test-deployment", "default", false, false);
+    let result = reconcile(Arc::new(obj), state.clone()).await.unwrap();
+    assert_eq!(result, Action::await_change());
+
+    let mut scaled = create_test_object("test-deployment", "default", true, false);
+    scaled.data["spec"]["replicas"] = json!(5);
+    reconcile(Arc::new(scaled), state).await.unwrap();
+
+    executor.assert_commands(&["reconcile"]);
+    assert!(
+        metrics()
+            .encode()
+            .contains("nuop_reconciles_filtered_total{script=\"test-filtered\"} 1")
+    );
+}
+
+#[tokio::test]
+async fn test_finalize_waits_for_higher_priority_finalizers() {
+    let (mock_service, _handle) = mock::pair::<Request<Body>, Response<Body>>();
+    let client = Client::new(mock_service, "default");
+
+    let config = create_test_config();
+    let api_resource = ApiResource::from_gvk(&(&config).into());
+    let executor = RecordingExecutor::new(StubExecutor::default());
+    let mut state = State::new(
+        api_resource,
+        client,
+        config,
+        get_test_script_path("success-no-changes"),
+        executor.clone(),
+    );
+    state.finalize_after = vec!["first.example.com/finalizer".to_string()];
+    let state = Arc::new(state);
+
+    let mut obj = create_test_object("test-deployment", "default", true, true);
+    obj.metadata
+        .finalizers
+        .as_mut()
+        .unwrap()
+        .insert(0, "first.example.com/finalizer".to_string());
+
+    let result = reconcile(Arc::new(obj), state).await.unwrap();
+    assert_eq!(result, Action::await_change());
+    assert!(executor.invocations().is_empty());
+}
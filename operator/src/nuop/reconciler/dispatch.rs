@@ -0,0 +1,118 @@
+use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use futures::channel::mpsc::UnboundedSender;
+use kube::{
+    ResourceExt,
+    api::{DynamicObject, GroupVersionKind},
+    runtime::watcher,
+};
+use tokio::sync::mpsc;
+use tracing::error;
+
+use super::{
+    config::Config,
+    predicate::PredicateCache,
+    state::{State, attempt_key},
+};
+
+/// Groups scripts by the kind they watch, ordered by priority, so each kind
+/// is watched once. A script is rejected when another script on the same
+/// kind already claims one of its finalizers.
+pub(crate) fn group_by_kind(scripts: Vec<(PathBuf, Config)>) -> Vec<Vec<(PathBuf, Config)>> {
+    let mut groups: Vec<(GroupVersionKind, Vec<(PathBuf, Config)>)> = Vec::new();
+    let mut claimed: HashMap<(GroupVersionKind, String), PathBuf> = HashMap::new();
+
+    'scripts: for (script, config) in scripts {
+        let gvk: GroupVersionKind = (&config).into();
+        for finalizer in config.finalizers() {
+            if let Some(first) = claimed.get(&(gvk.clone(), finalizer.to_string())) {
+                error!(
+                    "Finalizer {} of script {:?} is already claimed by {:?} for kind {}",
+                    finalizer, script, first, gvk.kind
+                );
+                continue 'scripts;
+            }
+        }
+        for finalizer in config.finalizers() {
+            claimed.insert((gvk.clone(), finalizer.to_string()), script.clone());
+        }
+
+        match groups.iter_mut().find(|(kind, _)| *kind == gvk) {
+            Some((_, group)) => group.push((script, config)),
+            None => groups.push((gvk, vec![(script, config)])),
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|(_, mut group)| {
+            group.sort_by_key(|(_, config)| Reverse(config.priority));
+            group
+        })
+        .collect()
+}
+
+/// Selectors shared by all scripts of a kind are applied by the API server,
+/// the others are evaluated per script.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct ClientSide {
+    pub labels: bool,
+    pub fields: bool,
+}
+
+/// A script on a shared watch and the channels feeding its controller.
+pub(crate) struct Target {
+    pub state: Arc<State>,
+    pub predicates: Mutex<PredicateCache>,
+    pub objects: UnboundedSender<Result<DynamicObject, watcher::Error>>,
+    pub deleted: Option<mpsc::UnboundedSender<DynamicObject>>,
+}
+
+impl Target {
+    /// Whether the object is meant for this script. Objects being deleted
+    /// that still carry one of the script's finalizers are always selected,
+    /// so a label change cannot strand them without `finalize`.
+    pub fn selects(&self, obj: &DynamicObject, client_side: ClientSide) -> bool {
+        let config = &self.state.config;
+        if obj.metadata.deletion_timestamp.is_some()
+            && config
+                .finalizers()
+                .iter()
+                .any(|f| obj.finalizers().iter().any(|held| held == f))
+        {
+            return true;
+        }
+        let labels_match = !client_side.labels || config.matches_labels(obj.labels());
+        let fields_match = !client_side.fields
+            || config.matches_fields(&serde_json::to_value(obj).unwrap_or_default());
+        labels_match && fields_match
+    }
+
+    /// Whether one of the script's predicates changed, see [`PredicateCache`].
+    pub fn changed(&self, obj: &DynamicObject) -> bool {
+        self.predicates
+            .lock()
+            .expect("predicates lock poisoned")
+            .changed(obj)
+    }
+
+    /// Drops what is kept per object once it is deleted.
+    pub fn forget(&self, obj: &DynamicObject) {
+        self.state.reset_attempts(&attempt_key(obj));
+        self.predicates
+            .lock()
+            .expect("predicates lock poisoned")
+            .forget(obj);
+    }
+}
+
+/// The value shared by all items, if they agree.
+pub(crate) fn common<T: PartialEq>(mut values: impl Iterator<Item = T>) -> Option<T> {
+    let first = values.next()?;
+    values.all(|v| v == first).then_some(first)
+}
@@ -0,0 +1,117 @@
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use http::{Request, Response};
+use k8s_openapi::{apimachinery::pkg::apis::meta::v1::Time, chrono::Utc};
+use kube::{Client, api::ApiResource, client::Body};
+use tower_test::mock;
+
+use super::{
+    config::Config,
+    dispatch::{ClientSide, Target, common, group_by_kind},
+    predicate::PredicateCache,
+    state::State,
+};
+use crate::nuop::testing::object_from_yaml;
+
+fn script(yaml: &str) -> (PathBuf, Config) {
+    let config: Config = serde_yaml::from_str(yaml).unwrap();
+    (PathBuf::from(format!("{}/mod.nu", config.name)), config)
+}
+
+fn names(group: &[(PathBuf, Config)]) -> Vec<&str> {
+    group.iter().map(|(_, c)| c.name.as_str()).collect()
+}
+
+#[test]
+fn test_group_by_kind_orders_by_priority() {
+    let groups = group_by_kind(vec![
+        script("{name: a, version: v1, kind: Secret}"),
+        script("{name: b, version: v1, kind: ConfigMap}"),
+        script("{name: c, version: v1, kind: Secret, priority: 10}"),
+        script("{name: d, group: other, version: v1, kind: Secret}"),
+        script("{name: e, version: v1, kind: Secret, priority: -1}"),
+        script("{name: f, version: v1, kind: Secret}"),
+    ]);
+
+    let groups: Vec<Vec<&str>> = groups.iter().map(|g| names(g)).collect();
+    assert_eq!(groups, vec![vec!["c", "a", "f", "e"], vec!["b"], vec!["d"]]);
+}
+
+#[test]
+fn test_group_by_kind_rejects_finalizer_clashes() {
+    let groups = group_by_kind(vec![
+        script("{name: a, version: v1, kind: Secret, finalizer: example.com/cleanup}"),
+        script(
+            "{name: b, version: v1, kind: Secret, finalizers: [example.com/audit, example.com/cleanup]}",
+        ),
+        script("{name: c, version: v1, kind: Secret, finalizer: example.com/audit}"),
+        script("{name: d, version: v1, kind: ConfigMap, finalizer: example.com/cleanup}"),
+    ]);
+
+    let groups: Vec<Vec<&str>> = groups.iter().map(|g| names(g)).collect();
+    assert_eq!(groups, vec![vec!["a", "c"], vec!["d"]]);
+}
+
+#[test]
+fn test_common_selectors() {
+    assert_eq!(
+        common([Some("a=b"), Some("a=b")].into_iter()),
+        Some(Some("a=b"))
+    );
+    assert_eq!(common([Some("a=b"), None].into_iter()), None);
+    assert_eq!(common([None::<&str>].into_iter()), Some(None));
+}
+
+fn target(yaml: &str) -> Target {
+    let (mock_service, _) = mock::pair::<Request<Body>, Response<Body>>();
+    let client = Client::new(mock_service, "default");
+    let (script, config) = script(yaml);
+    let api_resource = ApiResource::from_gvk(&(&config).into());
+    let (objects, _) = futures::channel::mpsc::unbounded();
+    Target {
+        state: Arc::new(State::new_default(api_resource, client, config, script)),
+        predicates: Mutex::new(PredicateCache::new(vec![])),
+        objects,
+        deleted: None,
+    }
+}
+
+#[tokio::test]
+async fn test_forget_resets_attempts() {
+    let target = target("{name: a, version: v1, kind: ConfigMap}");
+    let obj = object_from_yaml(
+        "apiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: settings\n  namespace: team-a\n",
+    )
+    .unwrap();
+
+    target.state.next_attempt("team-a/settings", false);
+    target.state.next_attempt("team-a/settings", false);
+    target.forget(&obj);
+
+    assert_eq!(target.state.next_attempt("team-a/settings", false), 1);
+}
+
+#[tokio::test]
+async fn test_selects_deleting_objects_holding_the_finalizer() {
+    let target = target(
+        "{name: a, version: v1, kind: ConfigMap, finalizer: example.com/cleanup, labelSelectors: {app: web}}",
+    );
+    let client_side = ClientSide {
+        labels: true,
+        fields: false,
+    };
+    let mut obj = object_from_yaml(
+        "apiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: settings\n  namespace: team-a\n  labels: {app: db}\n  finalizers: [example.com/cleanup]\n",
+    )
+    .unwrap();
+    assert!(!target.selects(&obj, client_side));
+
+    obj.metadata.deletion_timestamp = Some(Time(Utc::now()));
+    assert!(target.selects(&obj, client_side));
+
+    obj.metadata.finalizers = Some(vec!["example.com/other".to_string()]);
+    assert!(!target.selects(&obj, client_side));
+}
@@ -0,0 +1,157 @@
+//! Filter expressions in CEL, evaluated against the watched object before the
+//! script is started. Expressions are compiled and run by the `cel` crate
+//! with its standard library, with the object bound to `object`.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    str::FromStr,
+    sync::{Arc, LazyLock},
+};
+
+use cel::{
+    Context, Env, IdedExpr, Program, Value,
+    common::ast::{EntryExpr, Expr},
+};
+use kube::api::DynamicObject;
+
+/// The variable the watched object is bound to.
+pub const OBJECT_VARIABLE: &str = "object";
+
+static ENV: LazyLock<Arc<Env>> = LazyLock::new(|| Arc::new(Env::stdlib()));
+
+#[derive(Clone)]
+pub struct Filter {
+    source: String,
+    program: Arc<Program>,
+}
+
+impl fmt::Debug for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Filter").field(&self.source).finish()
+    }
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let program = ENV.compile(source).map_err(|e| e.to_string())?;
+        let mut scope = vec![OBJECT_VARIABLE];
+        if let Some(variable) = undeclared(program.expression(), &mut scope) {
+            return Err(format!("undeclared reference to '{variable}'"));
+        }
+        Ok(Filter {
+            source: source.to_string(),
+            program: Arc::new(program),
+        })
+    }
+}
+
+impl Filter {
+    /// Whether the object passes the filter. Evaluation errors, such as
+    /// accessing a missing field, are returned so callers can log them.
+    pub fn matches(&self, obj: &DynamicObject) -> Result<bool, String> {
+        let object = serde_json::to_value(obj).map_err(|e| e.to_string())?;
+        let mut context = Context::with_env(ENV.clone());
+        context.add_variable_from_value(OBJECT_VARIABLE, to_cel(object));
+        match self.program.execute(&context).map_err(|e| e.to_string())? {
+            Value::Bool(result) => Ok(result),
+            other => Err(format!("filter returned {other:?} instead of a bool")),
+        }
+    }
+}
+
+/// Type names usable as identifiers, as in `type(x) == int`.
+const TYPE_NAMES: [&str; 10] = [
+    "bool",
+    "bytes",
+    "double",
+    "int",
+    "list",
+    "map",
+    "null_type",
+    "string",
+    "type",
+    "uint",
+];
+
+/// The first identifier that is neither in scope nor a type name. The
+/// variables of macros such as `exists(x, p)` are in scope within them.
+fn undeclared<'a>(expr: &'a IdedExpr, scope: &mut Vec<&'a str>) -> Option<&'a str> {
+    match &expr.expr {
+        Expr::Ident(name) => (!name.starts_with('@')
+            && !scope.contains(&name.as_str())
+            && !TYPE_NAMES.contains(&name.as_str()))
+        .then_some(name.as_str()),
+        Expr::Call(call) => call
+            .target
+            .iter()
+            .map(|target| target.as_ref())
+            .chain(&call.args)
+            .find_map(|e| undeclared(e, scope)),
+        Expr::Comprehension(comprehension) => {
+            if let Some(name) = undeclared(&comprehension.iter_range, scope) {
+                return Some(name);
+            }
+            let outer = scope.len();
+            scope.push(&comprehension.iter_var);
+            scope.extend(comprehension.iter_var2.as_deref());
+            scope.push(&comprehension.accu_var);
+            let found = [
+                &comprehension.accu_init,
+                &comprehension.loop_cond,
+                &comprehension.loop_step,
+                &comprehension.result,
+            ]
+            .into_iter()
+            .find_map(|e| undeclared(e, scope));
+            scope.truncate(outer);
+            found
+        }
+        Expr::List(list) => list.elements.iter().find_map(|e| undeclared(e, scope)),
+        Expr::Map(map) => map.entries.iter().find_map(|entry| match &entry.expr {
+            EntryExpr::MapEntry(e) => {
+                undeclared(&e.key, scope).or_else(|| undeclared(&e.value, scope))
+            }
+            EntryExpr::StructField(f) => undeclared(&f.value, scope),
+        }),
+        Expr::Struct(s) => s.entries.iter().find_map(|entry| match &entry.expr {
+            EntryExpr::MapEntry(e) => {
+                undeclared(&e.key, scope).or_else(|| undeclared(&e.value, scope))
+            }
+            EntryExpr::StructField(f) => undeclared(&f.value, scope),
+        }),
+        Expr::Select(select) => undeclared(&select.operand, scope),
+        Expr::Literal(_) | Expr::Unspecified => None,
+    }
+}
+
+/// Converts JSON the way Kubernetes binds objects for CEL: integral numbers
+/// become `int`, others `double`.
+fn to_cel(value: serde_json::Value) -> Value {
+    match value {
+        serde_json::Value::Null => Value::Null,
+        serde_json::Value::Bool(b) => Value::Bool(b),
+        serde_json::Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+            (Some(i), _) => Value::Int(i),
+            (None, Some(u)) => Value::UInt(u),
+            (None, None) => Value::Float(n.as_f64().unwrap_or_default()),
+        },
+        serde_json::Value::String(s) => s.into(),
+        serde_json::Value::Array(items) => items.into_iter().map(to_cel).collect::<Vec<_>>().into(),
+        serde_json::Value::Object(fields) => fields
+            .into_iter()
+            .map(|(key, value)| (key, to_cel(value)))
+            .collect::<HashMap<_, _>>()
+            .into(),
+    }
+}
+
+pub(crate) fn filter_errors(filter: Option<&str>) -> Vec<String> {
+    filter
+        .and_then(|f| f.parse::<Filter>().err())
+        .map(|e| format!("invalid filter: {e}"))
+        .into_iter()
+        .collect()
+}
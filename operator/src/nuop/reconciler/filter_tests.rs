@@ -0,0 +1,131 @@
+use kube::api::DynamicObject;
+use serde_json::json;
+
+use super::filter::{Filter, filter_errors};
+
+fn create_object() -> DynamicObject {
+    serde_json::from_value(json!({
+        "apiVersion": "apps/v1",
+        "kind": "Deployment",
+        "metadata": {
+            "name": "web",
+            "namespace": "default",
+            "labels": { "app": "web", "env": "prod" },
+            "annotations": { "example.com/managed": "true" }
+        },
+        "spec": {
+            "replicas": 3,
+            "template": {
+                "spec": {
+                    "containers": [
+                        { "name": "web", "image": "nginx:1.27" },
+                        { "name": "proxy", "image": "envoy:1.30" }
+                    ]
+                }
+            }
+        }
+    }))
+    .unwrap()
+}
+
+fn eval(expr: &str) -> Result<bool, String> {
+    expr.parse::<Filter>()?.matches(&create_object())
+}
+
+#[test]
+fn test_filter_expressions() {
+    let cases = [
+        (
+            "has(object.metadata.annotations) && !has(object.metadata.finalizers)",
+            true,
+        ),
+        (
+            "'example.com/managed' in object.metadata.annotations && object.spec.replicas > 1",
+            true,
+        ),
+        ("object.spec.replicas > 3", false),
+        ("object.spec.replicas * 2 - 1 == 5 && 7 % 4 == 3", true),
+        ("object.spec.replicas == 3.0 && 2.5 < 3", true),
+        ("object.metadata.labels.env in ['prod', 'staging']", true),
+        ("!(object.metadata.labels['env'] == 'prod')", false),
+        ("has(object.metadata.labels.tier)", false),
+        (
+            "has(object.status) && has(object.status.readyReplicas)",
+            false,
+        ),
+        (
+            "type(object.spec.replicas) == int && object.metadata.name.matches('^w.b$')",
+            true,
+        ),
+        ("size(object.spec.template.spec.containers) == 2", true),
+        (
+            "object.metadata.name.size() == 3 && object.metadata.name.startsWith('w')",
+            true,
+        ),
+        (
+            "object.spec.template.spec.containers.exists(c, c.image.startsWith('envoy'))",
+            true,
+        ),
+        (
+            "object.spec.template.spec.containers.all(c, c.image.contains(':'))",
+            true,
+        ),
+        (
+            "object.spec.template.spec.containers[1].name.endsWith('xy')",
+            true,
+        ),
+        ("object.metadata.labels.exists(k, k == 'app')", true),
+        (
+            "int('4') > object.spec.replicas ? string(1) == '1' : false",
+            true,
+        ),
+        ("double(object.spec.replicas) / 2.0 == 1.5", true),
+        (
+            "'a' + \"b\" == 'ab' && [1] + [2] == [1, 2] && -object.spec.replicas < 0",
+            true,
+        ),
+        // A decisive operand wins over an error on the other side
+        ("object.status.ready || object.spec.replicas == 3", true),
+        ("object.status.ready && false", false),
+    ];
+
+    for (expr, expected) in cases {
+        assert_eq!(eval(expr), Ok(expected), "{expr}");
+    }
+}
+
+#[test]
+fn test_filter_evaluation_errors() {
+    assert_eq!(
+        eval("object.status.ready"),
+        Err("No such key: status".to_string())
+    );
+    // Like in Kubernetes, has() needs the fields before the last one to exist
+    assert!(eval("has(object.status.readyReplicas)").is_err());
+    assert!(eval("object.spec.replicas / 0 == 1").is_err());
+    assert!(eval("object.spec.replicas").is_err());
+    assert!(eval("object.metadata.name > 1").is_err());
+    assert!(eval("lower(object.metadata.name) == 'web'").is_err());
+}
+
+#[test]
+fn test_filter_compile_errors() {
+    let error = |filter: &str| filter_errors(Some(filter)).join("\n");
+
+    assert!(error("object.spec.replicas >").contains("Syntax error"));
+    assert_eq!(
+        error("spec.replicas > 1"),
+        "invalid filter: undeclared reference to 'spec'"
+    );
+    assert_eq!(
+        error("object.spec.items.exists(x, y > 0)"),
+        "invalid filter: undeclared reference to 'y'"
+    );
+    assert!(error("has(object)").starts_with("invalid filter: "));
+    assert!(error("object.spec.items.exists(x)").starts_with("invalid filter: "));
+    assert!(error("object.metadata.name == 'a").starts_with("invalid filter: "));
+    assert!(error("(object.spec.replicas > 1").starts_with("invalid filter: "));
+    assert!(filter_errors(Some("object.spec.replicas > 1")).is_empty());
+    assert!(filter_errors(Some("object.spec.items.exists(x, x > 0)")).is_empty());
+    assert!(filter_errors(None).is_empty());
+}
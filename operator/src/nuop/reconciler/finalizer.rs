@@ -1,81 +1,302 @@
-use crate::nuop::util::to_kube_error;
-
-use super::config::ReconcilePhase;
-use kube::{Api, Error, ResourceExt, api::DynamicObject, runtime::controller::Action};
-use std::time::Duration;
-use tracing::info;
-
-pub fn detect_phase<'a>(obj: &DynamicObject, finalizer: Option<&'a str>) -> ReconcilePhase<'a> {
-    match finalizer {
-        None => ReconcilePhase::Noop("reconcile"),
-        Some(f) => {
-            let has = obj
-                .metadata
-                .finalizers
-                .as_ref()
-                .is_some_and(|fs| fs.contains(&f.to_string()));
-            let deleting = obj.metadata.deletion_timestamp.is_some();
-
-            match (deleting, has) {
-                (true, true) => ReconcilePhase::Finalizing,
-                (_, false) => ReconcilePhase::NeedsFinalizer,
-                _ => ReconcilePhase::Active,
+use crate::nuop::{
+    metrics::{ObjectLabels, ScriptLabels, metrics},
+    util::to_kube_error,
+};
+
+use super::config::{Config, FinalizePolicy, ReconcilePhase};
+use super::state::{CommandExecutor, State};
+use k8s_openapi::chrono::{DateTime, Utc};
+use kube::{
+    Api, Error, Resource, ResourceExt,
+    api::{DynamicObject, Patch, PatchParams},
+    runtime::{
+        controller::Action,
+        events::{Event, EventType, Recorder, Reporter},
+    },
+};
+use serde_json::{Value, json};
+use std::{env, time::Duration};
+use tracing::{debug, info, instrument, warn};
+
+/// Attempts at writing finalizers before giving up on repeated conflicts.
+const CONFLICT_RETRIES: u32 = 5;
+
+/// Message of the 422 returned when a JSON patch operation fails, as opposed
+/// to validation errors, which name the invalid field.
+const PATCH_REJECTED: &str = "the server rejected our request due to an error in our request";
+
+pub fn detect_phase<'a>(obj: &DynamicObject, finalizers: &[&'a str]) -> ReconcilePhase<'a> {
+    if finalizers.is_empty() {
+        return ReconcilePhase::Noop("reconcile");
+    }
+
+    let present = obj.finalizers();
+    let deleting = obj.metadata.deletion_timestamp.is_some();
+    let pending = finalizers.iter().find(|f| present.iter().any(|p| p == *f));
+    let missing = finalizers.iter().any(|f| !present.iter().any(|p| p == f));
+
+    // Finalizers cannot be added once an object is being deleted
+    match (deleting, pending, missing) {
+        (true, Some(f), _) => ReconcilePhase::Finalizing(f),
+        (false, _, true) => ReconcilePhase::NeedsFinalizer,
+        _ => ReconcilePhase::Active,
+    }
+}
+
+#[instrument(skip_all, fields(finalizers = ?finalizers, dry_run = dry_run))]
+pub async fn add_finalizer(
+    api: &Api<DynamicObject>,
+    obj: &DynamicObject,
+    finalizers: &[&str],
+    dry_run: bool,
+) -> Result<Action, Error> {
+    let added = patch_finalizers(api, obj, dry_run, |current| {
+        for finalizer in finalizers {
+            if !current.iter().any(|f| f == finalizer) {
+                current.push(finalizer.to_string());
             }
         }
+    })
+    .await
+    .map_err(|e| to_kube_error(&e.to_string(), "Failed to add finalizer", 500))?;
+
+    match added {
+        true if !dry_run => {
+            info!(
+                "Added finalizers {:?} to {}/{}",
+                finalizers,
+                obj.namespace().unwrap_or_default(),
+                obj.name_any()
+            );
+            Ok(Action::requeue(Duration::from_secs(5)))
+        }
+        _ => Ok(Action::await_change()),
     }
 }
 
-pub async fn add_finalizer(
+#[instrument(skip_all, fields(finalizer = %finalizer, dry_run = dry_run))]
+pub async fn remove_finalizer(
     api: &Api<DynamicObject>,
     obj: &DynamicObject,
     finalizer: &str,
+    dry_run: bool,
 ) -> Result<Action, Error> {
-    let mut obj = obj.clone();
-    let finalizers = obj.metadata.finalizers.get_or_insert_with(Vec::new);
-
-    if !finalizers.contains(&finalizer.to_string()) {
-        finalizers.push(finalizer.to_string());
-        api.replace(&obj.name_any(), &Default::default(), &obj)
-            .await
-            .map_err(|e| to_kube_error(&e.to_string(), "Failed to add finalizer", 500))?;
+    let removed = patch_finalizers(api, obj, dry_run, |current| {
+        current.retain(|f| f != finalizer)
+    })
+    .await
+    .map_err(|e| to_kube_error(&e.to_string(), "Failed to remove finalizer", 500))?;
 
+    if removed && !dry_run {
         info!(
-            "Added finalizer to {}/{}",
+            "Removed finalizer {} from {}/{}",
+            finalizer,
             obj.namespace().unwrap_or_default(),
             obj.name_any()
         );
-        return Ok(Action::requeue(Duration::from_secs(5)));
     }
 
     Ok(Action::await_change())
 }
 
-pub async fn remove_finalizer(
+/// Rewrites the finalizers of `obj` with a JSON patch that first tests the
+/// resourceVersion, so concurrent edits are never clobbered. On a conflict the
+/// object is fetched again and `update` reapplied. Returns whether anything
+/// changed.
+async fn patch_finalizers<F>(
     api: &Api<DynamicObject>,
     obj: &DynamicObject,
+    dry_run: bool,
+    update: F,
+) -> Result<bool, Error>
+where
+    F: Fn(&mut Vec<String>),
+{
+    let name = obj.name_any();
+    let mut current = obj.clone();
+
+    for _ in 0..CONFLICT_RETRIES {
+        let mut finalizers = current.finalizers().to_vec();
+        update(&mut finalizers);
+        if finalizers == current.finalizers() {
+            return Ok(false);
+        }
+
+        if dry_run {
+            info!(
+                "Dry run: would patch {}/{} with finalizers {:?}",
+                current.namespace().unwrap_or_default(),
+                name,
+                finalizers
+            );
+            return Ok(true);
+        }
+
+        let mut ops = Vec::new();
+        if let Some(resource_version) = current.resource_version() {
+            ops.push(json!({
+                "op": "test",
+                "path": "/metadata/resourceVersion",
+                "value": resource_version,
+            }));
+        }
+        ops.push(json!({
+            "op": "add",
+            "path": "/metadata/finalizers",
+            "value": finalizers,
+        }));
+        let patch = serde_json::from_value(Value::Array(ops))
+            .map_err(|e| to_kube_error(&e.to_string(), "Invalid finalizer patch", 500))?;
+
+        match api
+            .patch(&name, &PatchParams::default(), &Patch::Json::<()>(patch))
+            .await
+        {
+            Ok(_) => return Ok(true),
+            // A failed test operation is reported as 422, a stale write as 409
+            Err(Error::Api(e))
+                if e.code == 409 || (e.code == 422 && e.message == PATCH_REJECTED) =>
+            {
+                debug!("Finalizers of {} changed concurrently, retrying", name);
+                current = api.get(&name).await?;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    Err(to_kube_error(
+        &format!("object kept changing after {CONFLICT_RETRIES} attempts"),
+        "Conflict updating finalizers",
+        409,
+    ))
+}
+
+/// Whether a failing finalize has run past its deadline, measured from the
+/// deletion timestamp, or used up its retries.
+pub fn finalize_expired(
+    config: &Config,
+    obj: &DynamicObject,
+    attempt: u32,
+    now: DateTime<Utc>,
+) -> bool {
+    let timed_out = config
+        .finalize_timeout
+        .zip(obj.metadata.deletion_timestamp.as_ref())
+        .is_some_and(|(timeout, deleted)| {
+            now.signed_duration_since(deleted.0).num_seconds() >= timeout as i64
+        });
+    let exhausted = config
+        .finalize_max_retries
+        .is_some_and(|retries| attempt > retries);
+
+    timed_out || exhausted
+}
+
+/// Handles a failed `finalize` invocation, force-removing the finalizer once
+/// expired if the policy allows it and otherwise passing the error on.
+#[instrument(skip_all, fields(finalizer = %finalizer, attempt = attempt))]
+pub async fn finalize_failed<E>(
+    api: &Api<DynamicObject>,
+    obj: &DynamicObject,
+    ctx: &State<E>,
     finalizer: &str,
-) -> Result<Action, Error> {
-    let mut obj = obj.clone();
-
-    obj.metadata.finalizers = Some(
-        obj.metadata
-            .finalizers
-            .take()
-            .unwrap_or_default()
-            .into_iter()
-            .filter(|f| f != finalizer)
-            .collect(),
-    );
+    attempt: u32,
+    error: Error,
+) -> Result<Action, Error>
+where
+    E: CommandExecutor,
+{
+    let config = &ctx.config;
+    let namespace = obj.namespace().unwrap_or_default();
+    let labels = ObjectLabels {
+        script: config.name.clone(),
+        namespace: namespace.clone(),
+        name: obj.name_any(),
+    };
 
-    api.replace(&obj.name_any(), &Default::default(), &obj)
-        .await
-        .map_err(|e| to_kube_error(&e.to_string(), "Failed to remove finalizer", 500))?;
+    metrics()
+        .finalize_failures
+        .get_or_create(&ScriptLabels {
+            script: config.name.clone(),
+        })
+        .inc();
+    metrics().finalize_pending.get_or_create(&labels).set(1);
 
-    info!(
-        "Removed finalizer from {}/{}",
-        obj.namespace().unwrap_or_default(),
-        obj.name_any()
+    if !finalize_expired(config, obj, attempt, Utc::now()) {
+        warn!(
+            "Finalize attempt {} failed for {}/{}, finalizer {} still pending",
+            attempt,
+            namespace,
+            obj.name_any(),
+            finalizer
+        );
+        return Err(error);
+    }
+
+    if config.finalize_policy == FinalizePolicy::Block {
+        warn!(
+            "Finalize for {}/{} expired after {} attempts, finalizer {} keeps blocking deletion",
+            namespace,
+            obj.name_any(),
+            attempt,
+            finalizer
+        );
+        return Err(error);
+    }
+
+    warn!(
+        "Finalize for {}/{} expired after {} attempts, force-removing finalizer {}",
+        namespace,
+        obj.name_any(),
+        attempt,
+        finalizer
     );
+    let action = remove_finalizer(api, obj, finalizer, config.dry_run).await?;
+    metrics().finalize_pending.remove(&labels);
+    metrics()
+        .finalizers_forced
+        .get_or_create(&ScriptLabels {
+            script: config.name.clone(),
+        })
+        .inc();
 
-    Ok(Action::await_change())
+    if !config.dry_run {
+        publish_forced_event(ctx, obj, finalizer, attempt).await;
+    }
+    Ok(action)
+}
+
+/// Clears the pending state once `finalize` succeeds.
+pub fn finalize_succeeded(config: &Config, obj: &DynamicObject) {
+    metrics().finalize_pending.remove(&ObjectLabels {
+        script: config.name.clone(),
+        namespace: obj.namespace().unwrap_or_default(),
+        name: obj.name_any(),
+    });
+}
+
+async fn publish_forced_event<E>(ctx: &State<E>, obj: &DynamicObject, finalizer: &str, attempt: u32)
+where
+    E: CommandExecutor,
+{
+    let reporter = Reporter {
+        controller: format!("nuop-{}", ctx.config.name),
+        instance: env::var("HOSTNAME").ok(),
+    };
+    let event = Event {
+        type_: EventType::Warning,
+        reason: "FinalizerForceRemoved".to_string(),
+        note: Some(format!(
+            "Removed finalizer {finalizer} after finalize failed {attempt} times"
+        )),
+        action: "Finalize".to_string(),
+        secondary: None,
+    };
+
+    if let Err(e) = Recorder::new(ctx.client.clone(), reporter)
+        .publish(&event, &obj.object_ref(&ctx.api_resource))
+        .await
+    {
+        warn!("Failed to publish event for {}: {:?}", obj.name_any(), e);
+    }
 }
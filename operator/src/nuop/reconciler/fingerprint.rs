@@ -0,0 +1,134 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use kube::{
+    Api, Error, ResourceExt,
+    api::{DynamicObject, Patch, PatchParams},
+};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+use crate::nuop::util::to_kube_error;
+
+pub const RECONCILED_ANNOTATION_PREFIX: &str = "reconciled.nuop.kemper.buzz";
+
+/// Annotation recording the last successful reconcile of `script`.
+pub fn annotation_key(script: &str) -> String {
+    format!("{RECONCILED_ANNOTATION_PREFIX}/{script}")
+}
+
+/// Hashes the script's own files: everything below its directory for a
+/// `mod.nu` module, otherwise only the script itself. Symlinks below the
+/// directory are hashed by their target path rather than followed.
+pub fn script_hash(script: &Path) -> String {
+    let mut files = Vec::new();
+    match script.parent() {
+        Some(dir) if script.file_name() == Some("mod.nu".as_ref()) => {
+            collect_files(dir, &mut files)
+        }
+        _ => {
+            if let Ok(content) = fs::read(script) {
+                files.push((script.to_path_buf(), content));
+            }
+        }
+    }
+    files.sort();
+
+    let mut hasher = Sha256::new();
+    for (file, content) in files {
+        hasher.update(file.to_string_lossy().as_bytes());
+        hasher.update(content);
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<(PathBuf, Vec<u8>)>) {
+    for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        let Ok(metadata) = fs::symlink_metadata(&path) else {
+            continue;
+        };
+        if metadata.is_dir() {
+            collect_files(&path, files);
+        } else if metadata.is_symlink() {
+            if let Ok(target) = fs::read_link(&path) {
+                files.push((path, target.to_string_lossy().as_bytes().to_vec()));
+            }
+        } else if let Ok(content) = fs::read(&path) {
+            files.push((path, content));
+        }
+    }
+}
+
+/// Hashes what a reconcile depends on: the object's content without status,
+/// its labels and annotations other than the reconciled markers, and the
+/// script.
+pub fn input_hash(obj: &DynamicObject, script_hash: &str) -> String {
+    let mut data = obj.data.clone();
+    if let Some(fields) = data.as_object_mut() {
+        fields.remove("status");
+    }
+    let marker = format!("{RECONCILED_ANNOTATION_PREFIX}/");
+    let annotations: BTreeMap<_, _> = obj
+        .annotations()
+        .iter()
+        .filter(|(key, _)| !key.starts_with(&marker))
+        .collect();
+
+    let mut hasher = Sha256::new();
+    hasher.update(data.to_string());
+    hasher.update(json!({ "labels": obj.labels(), "annotations": annotations }).to_string());
+    hasher.update(script_hash);
+    format!("{:x}", hasher.finalize())
+}
+
+/// Whether the object was already reconciled with this input hash, and
+/// recently enough when a full resync interval is configured.
+pub fn is_unchanged(
+    obj: &DynamicObject,
+    script: &str,
+    hash: &str,
+    resync_seconds: Option<u64>,
+    now: i64,
+) -> bool {
+    let Some(recorded) = obj.annotations().get(&annotation_key(script)) else {
+        return false;
+    };
+    let Some((recorded_hash, reconciled_at)) = recorded.split_once('@') else {
+        return false;
+    };
+
+    let within_resync = match (resync_seconds, reconciled_at.parse::<i64>()) {
+        (None, _) => true,
+        (Some(resync), Ok(at)) => now - at < resync as i64,
+        (Some(_), Err(_)) => false,
+    };
+    recorded_hash == hash && within_resync
+}
+
+/// Records a successful reconcile in the object's annotations.
+pub async fn record_reconciled(
+    api: &Api<DynamicObject>,
+    obj: &DynamicObject,
+    script: &str,
+    hash: &str,
+    now: i64,
+) -> Result<(), Error> {
+    let patch = json!({
+        "metadata": {
+            "annotations": { annotation_key(script): format!("{hash}@{now}") }
+        }
+    });
+
+    api.patch(
+        &obj.name_any(),
+        &PatchParams::default(),
+        &Patch::Merge(&patch),
+    )
+    .await
+    .map_err(|e| to_kube_error(&e.to_string(), "Failed to record reconcile", 500))?;
+    Ok(())
+}
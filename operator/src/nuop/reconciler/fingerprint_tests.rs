@@ -0,0 +1,135 @@
+use std::fs;
+
+use kube::{ResourceExt, api::DynamicObject};
+use serde_json::json;
+
+use super::fingerprint::{annotation_key, input_hash, is_unchanged, script_hash};
+
+fn create_object(replicas: u32, annotation: Option<&str>) -> DynamicObject {
+    let mut obj: DynamicObject = serde_json::from_value(json!({
+        "apiVersion": "apps/v1",
+        "kind": "Deployment",
+        "metadata": { "name": "web", "namespace": "default" },
+        "spec": { "replicas": replicas },
+        "status": { "readyReplicas": 0 }
+    }))
+    .unwrap();
+    if let Some(value) = annotation {
+        obj.metadata.annotations = Some(
+            [(annotation_key("scaler"), value.to_string())]
+                .into_iter()
+                .collect(),
+        );
+    }
+    obj
+}
+
+#[test]
+fn test_input_hash_ignores_status_and_metadata() {
+    let obj = create_object(3, None);
+    let hash = input_hash(&obj, "script");
+
+    let mut status_changed = obj.clone();
+    status_changed.data["status"]["readyReplicas"] = json!(3);
+    status_changed.metadata.resource_version = Some("2".to_string());
+    assert_eq!(input_hash(&status_changed, "script"), hash);
+    assert_eq!(
+        input_hash(&create_object(3, Some("abc@1000")), "script"),
+        hash
+    );
+
+    assert_ne!(input_hash(&create_object(4, None), "script"), hash);
+    assert_ne!(input_hash(&obj, "other-script"), hash);
+}
+
+#[test]
+fn test_input_hash_covers_labels_and_annotations() {
+    let obj = create_object(3, Some("abc@1000"));
+    let hash = input_hash(&obj, "script");
+
+    let mut labelled = obj.clone();
+    labelled
+        .labels_mut()
+        .insert("tier".to_string(), "frontend".to_string());
+    assert_ne!(input_hash(&labelled, "script"), hash);
+
+    let mut annotated = obj.clone();
+    annotated
+        .annotations_mut()
+        .insert("example.com/owner".to_string(), "team-a".to_string());
+    assert_ne!(input_hash(&annotated, "script"), hash);
+}
+
+#[test]
+fn test_is_unchanged_honours_hash_and_resync() {
+    let hash = input_hash(&create_object(3, None), "script");
+    let obj = create_object(3, Some(&format!("{hash}@1000")));
+
+    assert!(is_unchanged(&obj, "scaler", &hash, None, 5000));
+    assert!(!is_unchanged(&obj, "scaler", "different", None, 5000));
+    assert!(!is_unchanged(&obj, "other-script", &hash, None, 5000));
+
+    // A full resync is due once the interval has passed
+    assert!(is_unchanged(&obj, "scaler", &hash, Some(600), 1599));
+    assert!(!is_unchanged(&obj, "scaler", &hash, Some(600), 1600));
+
+    assert!(!is_unchanged(
+        &create_object(3, None),
+        "scaler",
+        &hash,
+        None,
+        0
+    ));
+    assert!(!is_unchanged(
+        &create_object(3, Some("garbage")),
+        "scaler",
+        &hash,
+        None,
+        0
+    ));
+}
+
+#[test]
+fn test_script_hash_covers_script_directory() {
+    let dir = tempfile::tempdir().unwrap();
+    let script = dir.path().join("mod.nu");
+    fs::write(&script, "def main [] {}").unwrap();
+    fs::create_dir(dir.path().join("lib")).unwrap();
+    fs::write(dir.path().join("lib/helpers.nu"), "export def a [] {}").unwrap();
+
+    let before = script_hash(&script);
+    assert_eq!(script_hash(&script), before);
+
+    fs::write(dir.path().join("lib/helpers.nu"), "export def b [] {}").unwrap();
+    assert_ne!(script_hash(&script), before);
+}
+
+#[test]
+fn test_script_hash_covers_only_a_loose_script() {
+    let dir = tempfile::tempdir().unwrap();
+    let script = dir.path().join("scaler.nu");
+    fs::write(&script, "def main [] {}").unwrap();
+
+    let before = script_hash(&script);
+    fs::write(dir.path().join("unrelated.txt"), "noise").unwrap();
+    assert_eq!(script_hash(&script), before);
+
+    fs::write(&script, "def main [] { 1 }").unwrap();
+    assert_ne!(script_hash(&script), before);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_script_hash_does_not_follow_symlinks() {
+    let outside = tempfile::tempdir().unwrap();
+    fs::write(outside.path().join("data.nu"), "export def a [] {}").unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    let script = dir.path().join("mod.nu");
+    fs::write(&script, "def main [] {}").unwrap();
+    std::os::unix::fs::symlink(outside.path(), dir.path().join("linked")).unwrap();
+
+    let before = script_hash(&script);
+    fs::write(outside.path().join("data.nu"), "export def b [] {}").unwrap();
+    assert_eq!(script_hash(&script), before);
+}
@@ -0,0 +1,130 @@
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, Mutex},
+    time::Duration,
+};
+
+use futures::{StreamExt, future};
+use kube::{
+    Api, Client, ResourceExt,
+    api::{ApiResource, DynamicObject, GroupVersionKind},
+    runtime::{
+        WatchStreamExt,
+        reflector::{Store, reflector, store::Writer},
+        watcher::{self, Config as WatcherConfig},
+    },
+};
+use serde_json::{Map, Value};
+use tracing::{info, warn};
+
+use super::config::{Lookup, render_label_selector};
+
+/// How long an invocation waits for the initial listing of a lookup kind.
+const READY_TIMEOUT: Duration = Duration::from_secs(30);
+
+type LookupKey = (GroupVersionKind, Option<String>, Option<String>);
+
+/// Stores by kind and selectors, shared by all scripts in the process.
+static STORES: LazyLock<Mutex<HashMap<LookupKey, Store<DynamicObject>>>> =
+    LazyLock::new(Default::default);
+
+impl Lookup {
+    fn gvk(&self) -> GroupVersionKind {
+        GroupVersionKind {
+            group: self.group.clone(),
+            version: self.version.clone(),
+            kind: self.kind.clone(),
+        }
+    }
+
+    /// Key of the lookup's objects in the rendered lookups.
+    pub fn key(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.kind)
+    }
+
+    fn field_selector(&self) -> Option<String> {
+        (!self.field_selectors.is_empty()).then(|| {
+            self.field_selectors
+                .iter()
+                .map(|(k, v)| format!("{k}={v}"))
+                .collect::<Vec<_>>()
+                .join(",")
+        })
+    }
+}
+
+/// Returns the cached store for a lookup, starting its reflector the first
+/// time the kind and selectors are requested.
+pub fn lookup_store(client: &Client, lookup: &Lookup) -> Store<DynamicObject> {
+    let label_selector = render_label_selector(&lookup.label_selectors, None);
+    let field_selector = lookup.field_selector();
+    let key = (lookup.gvk(), label_selector.clone(), field_selector.clone());
+
+    let mut stores = STORES.lock().expect("lookup stores lock poisoned");
+    if let Some(store) = stores.get(&key) {
+        return store.clone();
+    }
+
+    let api_resource = ApiResource::from_gvk(&key.0);
+    let api: Api<DynamicObject> = Api::all_with(client.clone(), &api_resource);
+    let writer = Writer::new(api_resource);
+    let store = writer.as_reader();
+    let watcher_config = WatcherConfig {
+        label_selector,
+        field_selector,
+        ..WatcherConfig::default()
+    };
+
+    info!("Caching {} for lookups", lookup.kind);
+    let kind = lookup.kind.clone();
+    tokio::spawn(
+        reflector(
+            writer,
+            watcher::watcher(api, watcher_config).default_backoff(),
+        )
+        .for_each(move |event| {
+            if let Err(e) = event {
+                warn!("Lookup watch of {} failed: {:?}", kind, e);
+            }
+            future::ready(())
+        }),
+    );
+
+    stores.insert(key, store.clone());
+    store
+}
+
+/// Renders the cached objects of each lookup, keyed by [`Lookup::key`],
+/// restricted to the object's namespace where requested.
+pub async fn render_lookups(
+    lookups: &[(Lookup, Store<DynamicObject>)],
+    obj: &DynamicObject,
+) -> Option<Value> {
+    if lookups.is_empty() {
+        return None;
+    }
+
+    let mut rendered = Map::new();
+    for (lookup, store) in lookups {
+        if tokio::time::timeout(READY_TIMEOUT, store.wait_until_ready())
+            .await
+            .is_err()
+        {
+            warn!("Lookup cache of {} is not ready yet", lookup.kind);
+        }
+
+        let mut objects: Vec<_> = store
+            .state()
+            .into_iter()
+            .filter(|cached| !lookup.same_namespace || cached.namespace() == obj.namespace())
+            .collect();
+        objects.sort_by_key(|cached| (cached.namespace(), cached.name_any()));
+
+        let objects = objects
+            .iter()
+            .filter_map(|cached| serde_json::to_value(cached.as_ref()).ok())
+            .collect();
+        rendered.insert(lookup.key().to_string(), Value::Array(objects));
+    }
+    Some(Value::Object(rendered))
+}
@@ -0,0 +1,107 @@
+use kube::{
+    api::{ApiResource, DynamicObject, GroupVersionKind},
+    runtime::{
+        reflector::{Store, store::Writer},
+        watcher::Event,
+    },
+};
+use serde_json::json;
+
+use super::{
+    config::{Lookup, lookup_errors},
+    lookup::render_lookups,
+};
+
+fn create_object(kind: &str, namespace: &str, name: &str) -> DynamicObject {
+    serde_json::from_value(json!({
+        "apiVersion": "v1",
+        "kind": kind,
+        "metadata": { "name": name, "namespace": namespace }
+    }))
+    .unwrap()
+}
+
+fn lookup(yaml: &str) -> Lookup {
+    serde_yaml::from_str(yaml).unwrap()
+}
+
+fn ready_store(objects: Vec<DynamicObject>) -> (Writer<DynamicObject>, Store<DynamicObject>) {
+    let mut writer = Writer::new(ApiResource::from_gvk(&GroupVersionKind {
+        group: String::new(),
+        version: "v1".to_string(),
+        kind: "Secret".to_string(),
+    }));
+    writer.apply_watcher_event(&Event::Init);
+    for obj in objects {
+        writer.apply_watcher_event(&Event::InitApply(obj));
+    }
+    writer.apply_watcher_event(&Event::InitDone);
+    let store = writer.as_reader();
+    (writer, store)
+}
+
+#[tokio::test]
+async fn test_render_lookups_by_key_and_namespace() {
+    let (_secrets_writer, secrets) = ready_store(vec![
+        create_object("Secret", "team-b", "token"),
+        create_object("Secret", "team-a", "token"),
+        create_object("Secret", "team-a", "cert"),
+    ]);
+    let (_namespaces_writer, namespaces) =
+        ready_store(vec![create_object("Namespace", "", "team-a")]);
+
+    let lookups = vec![
+        (
+            lookup("{version: v1, kind: Secret, sameNamespace: true}"),
+            secrets.clone(),
+        ),
+        (
+            lookup("{name: allSecrets, version: v1, kind: Secret}"),
+            secrets,
+        ),
+        (lookup("{version: v1, kind: Namespace}"), namespaces),
+    ];
+    let obj = create_object("ConfigMap", "team-a", "app");
+
+    let rendered = render_lookups(&lookups, &obj).await.unwrap();
+    let names = |key: &str| -> Vec<String> {
+        rendered[key]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|o| {
+                format!(
+                    "{}/{}",
+                    o["metadata"]["namespace"].as_str().unwrap_or_default(),
+                    o["metadata"]["name"].as_str().unwrap()
+                )
+            })
+            .collect()
+    };
+
+    assert_eq!(names("Secret"), vec!["team-a/cert", "team-a/token"]);
+    assert_eq!(
+        names("allSecrets"),
+        vec!["team-a/cert", "team-a/token", "team-b/token"]
+    );
+    assert_eq!(names("Namespace"), vec!["/team-a"]);
+
+    assert_eq!(render_lookups(&[], &obj).await, None);
+}
+
+#[test]
+fn test_lookup_errors() {
+    let lookups = vec![
+        lookup("{version: v1, kind: Secret, labelSelectors: {'bad key!': x}}"),
+        lookup("{version: v1, kind: Pod, fieldSelectors: {'spec..nodeName': n}}"),
+        lookup("{version: v1, kind: Namespace}"),
+    ];
+
+    assert_eq!(
+        lookup_errors(&lookups),
+        vec![
+            "lookup Secret: invalid label selector key 'bad key!'",
+            "lookup Pod: invalid field selector key 'spec..nodeName'",
+        ]
+    );
+}
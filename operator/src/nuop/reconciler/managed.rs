@@ -1,20 +1,19 @@
-use super::controller::controller as reconciler_controller;
+use super::{controller::controller as reconciler_controller, dispatch::group_by_kind};
 use crate::nuop::manager::Mapping;
 use crate::nuop::reconciler::util::get_script_config;
 use kube::Client;
 use std::fs::File;
 use std::io::BufReader;
-use std::{collections::HashSet, path::PathBuf};
+use std::path::PathBuf;
 use tokio::task::JoinHandle;
 use tracing::{error, warn};
 
+/// Spawns one controller per mapped kind, shared by all scripts of that kind.
 pub fn get_managed_controllers(
     client: &Client,
     mappings: &[PathBuf],
     scripts: &[PathBuf],
 ) -> Vec<JoinHandle<()>> {
-    let mut processed_kinds = HashSet::new();
-
     let mappings: Vec<Mapping> = mappings
         .iter()
         .filter_map(|m| {
@@ -31,7 +30,7 @@ pub fn get_managed_controllers(
         })
         .collect();
 
-    scripts
+    let configs = scripts
         .iter()
         .filter_map(|script| {
             get_script_config(script)
@@ -39,51 +38,60 @@ pub fn get_managed_controllers(
                     error!("Failed to get script config for {:?}: {:?}", script, e);
                 })
                 .ok()
-                .and_then(|mut config| {
-                    if let Some(mapping) = mappings.iter().find(|mapping| {
-                        mapping.name == config.name
-                            && mapping.group == config.group
-                            && mapping.kind == config.kind
-                            && mapping.version == config.version
-                    }) {
-                        let unique_key = (
-                            config.group.clone(),
-                            config.kind.clone(),
-                            config.version.clone(),
-                        );
-                        if processed_kinds.insert(unique_key.clone()) {
-                            if !mapping.field_selectors.is_empty() {
-                                config.field_selectors = mapping.field_selectors.clone();
-                            }
-                            if !mapping.label_selectors.is_empty() {
-                                config.label_selectors = mapping.label_selectors.clone();
-                            }
-                            if let Some(ran) = mapping.requeue_after_noop {
-                                config.requeue_after_noop = ran;
-                            };
-                            if let Some(rac) = mapping.requeue_after_change {
-                                config.requeue_after_change = rac;
-                            };
-                            Some((script.clone(), config))
-                        } else {
-                            error!(
-                                "Duplicate group, kind, and version found for script {:?}: {:?}",
-                                &script, unique_key
-                            );
-                            None
-                        }
-                    } else {
-                        warn!("No mapping present for {:?}", config);
-                        None
-                    }
-                })
+                .map(|config| (script, config))
         })
-        .map(|(script, config)| {
-            tokio::spawn(reconciler_controller(
-                client.clone(),
-                config,
-                script.clone(),
-            ))
+        .flat_map(|(script, config)| {
+            config
+                .watched()
+                .into_iter()
+                .map(move |config| (script, config))
         })
+        .filter_map(|(script, mut config)| {
+            if let Some(mapping) = mappings.iter().find(|mapping| {
+                mapping.name == config.name
+                    && mapping.group == config.group
+                    && mapping.kind == config.kind
+                    && mapping.version == config.version
+            }) {
+                if !mapping.field_selectors.is_empty() {
+                    config.field_selectors = mapping.field_selectors.clone();
+                }
+                if !mapping.label_selectors.is_empty() || mapping.label_selector.is_some() {
+                    config.label_selectors = mapping.label_selectors.clone();
+                    config.label_selector = mapping.label_selector.clone();
+                }
+                if let Some(ran) = mapping.requeue_after_noop {
+                    config.requeue_after_noop = ran;
+                };
+                if let Some(rac) = mapping.requeue_after_change {
+                    config.requeue_after_change = rac;
+                };
+                if !mapping.predicates.is_empty() {
+                    config.predicates = mapping.predicates.clone();
+                }
+                if mapping.filter.is_some() {
+                    config.filter = mapping.filter.clone();
+                }
+                if let Some(priority) = mapping.priority {
+                    config.priority = priority;
+                }
+                config.params.extend(mapping.resolve_params());
+                config
+                    .secret_params
+                    .extend(mapping.secret_params().map(str::to_string));
+                if let Some(dry_run) = mapping.dry_run {
+                    config.dry_run = dry_run;
+                };
+                Some((script.clone(), config))
+            } else {
+                warn!("No mapping present for {:?}", config);
+                None
+            }
+        })
+        .collect();
+
+    group_by_kind(configs)
+        .into_iter()
+        .map(|group| tokio::spawn(reconciler_controller(client.clone(), group)))
         .collect()
 }
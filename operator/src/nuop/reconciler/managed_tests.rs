@@ -63,7 +63,7 @@ async fn test_with_duplicate_kinds() {
 
     let controllers = get_managed_controllers(&client, &mappings, &scripts);
 
-    // Should only create 2 controllers (duplicate should be rejected)
+    // Should only create 2 controllers (the duplicate shares the Deployment controller)
     assert_eq!(controllers.len(), 2);
 
     // Clean up
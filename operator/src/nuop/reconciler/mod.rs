@@ -1,16 +1,59 @@
-mod config;
-mod controller;
-mod finalizer;
+pub(crate) mod children;
+pub(crate) mod config;
+pub(crate) mod controller;
+pub(crate) mod dispatch;
+pub(crate) mod filter;
+pub(crate) mod finalizer;
+pub(crate) mod fingerprint;
+pub(crate) mod lookup;
 pub mod managed;
+pub(crate) mod mutation;
+pub(crate) mod predicate;
+pub(crate) mod recorder;
+pub(crate) mod redaction;
+pub(crate) mod script_log;
 pub mod standard;
-mod state;
+pub(crate) mod state;
 pub mod util;
 
+#[cfg(test)]
+mod children_tests;
+
+#[cfg(test)]
+mod config_tests;
+
 #[cfg(test)]
 mod controller_tests;
 
+#[cfg(test)]
+mod dispatch_tests;
+
+#[cfg(test)]
+mod filter_tests;
+
+#[cfg(test)]
+mod fingerprint_tests;
+
+#[cfg(test)]
+mod lookup_tests;
+
 #[cfg(test)]
 mod managed_tests;
 
+#[cfg(test)]
+mod mutation_tests;
+
+#[cfg(test)]
+mod predicate_tests;
+
+#[cfg(test)]
+mod recorder_tests;
+
+#[cfg(test)]
+mod redaction_tests;
+
+#[cfg(test)]
+mod script_log_tests;
+
 #[cfg(test)]
 mod standard_tests;
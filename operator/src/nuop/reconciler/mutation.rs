@@ -0,0 +1,207 @@
+use kube::{
+    Api, Error, ResourceExt,
+    api::{DynamicObject, Patch, PatchParams},
+};
+use serde_json::{Map, Value};
+use tracing::info;
+
+use crate::nuop::util::to_kube_error;
+
+use super::children::parse_manifests;
+
+/// Fields maintained by the API server that a mutated copy must not touch.
+const SERVER_METADATA: &[&str] = &[
+    "managedFields",
+    "resourceVersion",
+    "uid",
+    "creationTimestamp",
+    "deletionTimestamp",
+    "deletionGracePeriodSeconds",
+    "generation",
+    "selfLink",
+];
+
+/// Parses the mutated copy printed by a mutating script and checks that it
+/// still refers to the watched object.
+pub(crate) fn parse_mutated(stdout: &str, obj: &DynamicObject) -> Result<DynamicObject, Error> {
+    let mut manifests = parse_manifests(stdout)?;
+    if manifests.len() != 1 {
+        return Err(to_kube_error(
+            &format!("expected exactly one object, got {}", manifests.len()),
+            "Invalid mutated object",
+            500,
+        ));
+    }
+
+    let mutated = manifests.remove(0);
+    if mutated.name_any() != obj.name_any() || mutated.types != obj.types {
+        return Err(to_kube_error(
+            "apiVersion, kind and metadata.name must match the watched object",
+            "Invalid mutated object",
+            500,
+        ));
+    }
+    Ok(mutated)
+}
+
+/// Computes the apply configuration needed to turn `current` into `mutated`,
+/// or `None` when nothing changed.
+///
+/// The configuration holds every changed field plus the fields `manager`
+/// already owns, so that previously applied fields are kept and fields the
+/// script dropped are released. The current resourceVersion is included as a
+/// precondition so a concurrent update makes the apply fail with a conflict.
+pub(crate) fn mutation_patch(
+    current: &DynamicObject,
+    mutated: &DynamicObject,
+    manager: &str,
+) -> Result<Option<Value>, Error> {
+    let to_value = |obj: &DynamicObject| {
+        serde_json::to_value(obj)
+            .map_err(|e| to_kube_error(&e.to_string(), "Failed to serialize object", 500))
+    };
+    let current_value = to_value(current)?;
+    let owned = owned_fields(&current_value, manager);
+    let current_value = strip(current_value);
+    let mutated_value = strip(to_value(mutated)?);
+
+    let previous = project(&owned, &current_value);
+    let desired = project(&owned, &mutated_value);
+    let changed = diff(&current_value, &mutated_value);
+    if changed.is_none() && previous == desired {
+        return Ok(None);
+    }
+
+    let mut patch = match (desired, changed) {
+        (Some(desired), Some(changed)) => merge(desired, changed),
+        (Some(value), None) | (None, Some(value)) => value,
+        (None, None) => Value::Object(Map::new()),
+    };
+
+    let types = current.types.clone().unwrap_or_default();
+    patch["apiVersion"] = Value::String(types.api_version);
+    patch["kind"] = Value::String(types.kind);
+    patch["metadata"]["name"] = Value::String(current.name_any());
+    if let Some(namespace) = current.namespace() {
+        patch["metadata"]["namespace"] = Value::String(namespace);
+    }
+    if let Some(resource_version) = current.resource_version() {
+        patch["metadata"]["resourceVersion"] = Value::String(resource_version);
+    }
+    Ok(Some(patch))
+}
+
+/// Applies the difference between `obj` and the script's mutated copy.
+/// Returns whether the object was changed.
+pub(crate) async fn apply_mutation(
+    api: &Api<DynamicObject>,
+    obj: &DynamicObject,
+    mutated: &DynamicObject,
+    script_name: &str,
+    dry_run: bool,
+) -> Result<bool, Error> {
+    let field_manager = format!("nuop-{script_name}");
+    let Some(patch) = mutation_patch(obj, mutated, &field_manager)? else {
+        info!("Mutated copy of {} is unchanged", obj.name_any());
+        return Ok(false);
+    };
+
+    if dry_run {
+        info!(
+            "Dry run: would apply {} as {}: {}",
+            obj.name_any(),
+            field_manager,
+            patch
+        );
+        return Ok(true);
+    }
+
+    api.patch(
+        &obj.name_any(),
+        &PatchParams::apply(&field_manager).force(),
+        &Patch::Apply(&patch),
+    )
+    .await
+    .map_err(|e| to_kube_error(&e.to_string(), "Failed to apply mutated object", 500))?;
+    info!("Applied mutation to {}", obj.name_any());
+    Ok(true)
+}
+
+fn strip(mut value: Value) -> Value {
+    if let Some(object) = value.as_object_mut() {
+        object.remove("apiVersion");
+        object.remove("kind");
+        object.remove("status");
+        if let Some(metadata) = object.get_mut("metadata").and_then(Value::as_object_mut) {
+            for field in SERVER_METADATA {
+                metadata.remove(*field);
+            }
+        }
+    }
+    value
+}
+
+/// Merges the fieldsV1 sets applied by `manager` into one field set.
+fn owned_fields(obj: &Value, manager: &str) -> Value {
+    obj["metadata"]["managedFields"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|entry| entry["manager"] == manager && entry["operation"] == "Apply")
+        .filter_map(|entry| entry.get("fieldsV1").cloned())
+        .fold(Value::Object(Map::new()), merge)
+}
+
+/// Selects the parts of `value` covered by a fieldsV1 set. Lists are treated
+/// as atomic and taken whole.
+fn project(fields: &Value, value: &Value) -> Option<Value> {
+    let (fields, object) = (fields.as_object()?, value.as_object()?);
+    let projected: Map<String, Value> = fields
+        .iter()
+        .filter_map(|(key, children)| {
+            let key = key.strip_prefix("f:")?;
+            let child = object.get(key)?;
+            let is_leaf = children.as_object().is_none_or(Map::is_empty) || !child.is_object();
+            let selected = if is_leaf {
+                child.clone()
+            } else {
+                project(children, child)?
+            };
+            Some((key.to_string(), selected))
+        })
+        .collect();
+    (!projected.is_empty()).then_some(Value::Object(projected))
+}
+
+/// Returns the fields of `desired` that are new or differ from `current`.
+fn diff(current: &Value, desired: &Value) -> Option<Value> {
+    match (current.as_object(), desired.as_object()) {
+        (Some(current), Some(desired)) => {
+            let changed: Map<String, Value> = desired
+                .iter()
+                .filter_map(|(key, value)| match current.get(key) {
+                    Some(existing) => diff(existing, value).map(|v| (key.clone(), v)),
+                    None => Some((key.clone(), value.clone())),
+                })
+                .collect();
+            (!changed.is_empty()).then_some(Value::Object(changed))
+        }
+        _ => (current != desired).then(|| desired.clone()),
+    }
+}
+
+fn merge(base: Value, overlay: Value) -> Value {
+    match (base, overlay) {
+        (Value::Object(mut base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                let merged = match base.remove(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => value,
+                };
+                base.insert(key, merged);
+            }
+            Value::Object(base)
+        }
+        (_, overlay) => overlay,
+    }
+}
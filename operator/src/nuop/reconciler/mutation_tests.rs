@@ -0,0 +1,185 @@
+use http::{Method, Request, Response};
+use kube::{
+    Api, Client,
+    api::{ApiResource, DynamicObject, GroupVersionKind},
+    client::Body,
+};
+use serde_json::{Value, json};
+use tower_test::mock;
+
+use super::mutation::{apply_mutation, mutation_patch, parse_mutated};
+
+const MANAGER: &str = "nuop-defaults";
+
+fn create_object(labels: Value, managed_fields: Value) -> DynamicObject {
+    serde_json::from_value(json!({
+        "apiVersion": "v1",
+        "kind": "ConfigMap",
+        "metadata": {
+            "name": "settings",
+            "namespace": "default",
+            "uid": "uid-1",
+            "resourceVersion": "42",
+            "labels": labels,
+            "managedFields": managed_fields
+        },
+        "data": { "key": "value" }
+    }))
+    .unwrap()
+}
+
+fn owned_labels(labels: &[&str]) -> Value {
+    let fields: serde_json::Map<String, Value> = labels
+        .iter()
+        .map(|label| (format!("f:{label}"), json!({})))
+        .collect();
+    json!([{
+        "manager": MANAGER,
+        "operation": "Apply",
+        "apiVersion": "v1",
+        "fieldsType": "FieldsV1",
+        "fieldsV1": { "f:metadata": { "f:labels": fields } }
+    }])
+}
+
+#[test]
+fn test_unchanged_copy_is_noop() {
+    let current = create_object(json!({ "team": "a" }), owned_labels(&["team"]));
+    let mut mutated = current.clone();
+    // Server-managed fields in the copy are ignored
+    mutated.metadata.managed_fields = None;
+    mutated.metadata.resource_version = None;
+
+    assert_eq!(mutation_patch(&current, &mutated, MANAGER).unwrap(), None);
+}
+
+#[test]
+fn test_patch_contains_only_changes_and_identity() {
+    let current = create_object(json!({ "team": "a" }), json!([]));
+    let mut mutated = current.clone();
+    mutated
+        .metadata
+        .labels
+        .as_mut()
+        .unwrap()
+        .insert("defaulted".to_string(), "true".to_string());
+
+    let patch = mutation_patch(&current, &mutated, MANAGER)
+        .unwrap()
+        .expect("patch expected");
+
+    assert_eq!(
+        patch,
+        json!({
+            "apiVersion": "v1",
+            "kind": "ConfigMap",
+            "metadata": {
+                "name": "settings",
+                "namespace": "default",
+                "resourceVersion": "42",
+                "labels": { "defaulted": "true" }
+            }
+        })
+    );
+}
+
+#[test]
+fn test_patch_keeps_and_releases_owned_fields() {
+    let current = create_object(
+        json!({ "team": "a", "tier": "web" }),
+        owned_labels(&["team", "tier"]),
+    );
+
+    // Adding a label keeps the previously applied ones in the configuration
+    let mut mutated = current.clone();
+    mutated
+        .metadata
+        .labels
+        .as_mut()
+        .unwrap()
+        .insert("zone".to_string(), "eu".to_string());
+    let patch = mutation_patch(&current, &mutated, MANAGER)
+        .unwrap()
+        .expect("patch expected");
+    assert_eq!(
+        patch["metadata"]["labels"],
+        json!({ "team": "a", "tier": "web", "zone": "eu" })
+    );
+
+    // Dropping an owned label leaves it out so the apply removes it
+    let mut mutated = current.clone();
+    mutated.metadata.labels.as_mut().unwrap().remove("tier");
+    let patch = mutation_patch(&current, &mutated, MANAGER)
+        .unwrap()
+        .expect("patch expected");
+    assert_eq!(patch["metadata"]["labels"], json!({ "team": "a" }));
+}
+
+#[test]
+fn test_parse_mutated_requires_same_object() {
+    let current = create_object(json!({}), json!([]));
+
+    let same = "apiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: settings\n";
+    assert!(parse_mutated(same, &current).is_ok());
+
+    let renamed = "apiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: other\n";
+    assert!(parse_mutated(renamed, &current).is_err());
+
+    let two = format!("{same}---\n{same}");
+    assert!(parse_mutated(&two, &current).is_err());
+}
+
+#[tokio::test]
+async fn test_apply_mutation_server_side_applies_difference() {
+    let (mock_service, mut handle) = mock::pair::<Request<Body>, Response<Body>>();
+    let client = Client::new(mock_service, "default");
+    let api: Api<DynamicObject> = Api::namespaced_with(
+        client,
+        "default",
+        &ApiResource::from_gvk(&GroupVersionKind::gvk("", "v1", "ConfigMap")),
+    );
+
+    let current = create_object(json!({}), json!([]));
+    let mut mutated = current.clone();
+    mutated.data["data"]["key"] = json!("normalized");
+
+    let server = tokio::spawn(async move {
+        let (request, send) = handle.next_request().await.expect("apply not called");
+        assert_eq!(request.method(), Method::PATCH);
+        assert_eq!(
+            request.uri().path(),
+            "/api/v1/namespaces/default/configmaps/settings"
+        );
+        assert!(
+            request
+                .uri()
+                .query()
+                .unwrap_or_default()
+                .contains("fieldManager=nuop-defaults")
+        );
+        let body = request.into_body().collect_bytes().await.unwrap();
+        let applied: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(applied["data"], json!({ "key": "normalized" }));
+        assert_eq!(applied["metadata"]["resourceVersion"], "42");
+        assert!(applied["metadata"].get("labels").is_none());
+
+        send.send_response(
+            Response::builder()
+                .status(200)
+                .body(Body::from(serde_json::to_vec(&applied).unwrap()))
+                .unwrap(),
+        );
+    });
+
+    let changed = apply_mutation(&api, &current, &mutated, "defaults", false)
+        .await
+        .expect("apply should succeed");
+    assert!(changed);
+    server.await.expect("mock server failed");
+
+    // No request is made when nothing changed
+    let changed = apply_mutation(&api, &current, &current, "defaults", false)
+        .await
+        .expect("noop should succeed");
+    assert!(!changed);
+}
@@ -0,0 +1,125 @@
+use std::{
+    collections::HashMap,
+    hash::{DefaultHasher, Hash, Hasher},
+    str::FromStr,
+};
+
+use kube::{
+    ResourceExt,
+    api::DynamicObject,
+    runtime::predicates::{annotations, generation, labels},
+};
+use serde_json::Value;
+
+/// A property of the watched object whose change triggers a reconcile.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WatchPredicate {
+    Generation,
+    Labels,
+    Annotations,
+    /// A dotted JSON path such as `.spec.replicas` or `.data.config`
+    Path(Vec<String>),
+}
+
+impl FromStr for WatchPredicate {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "generation" => Ok(WatchPredicate::Generation),
+            "labels" => Ok(WatchPredicate::Labels),
+            "annotations" => Ok(WatchPredicate::Annotations),
+            path if path.starts_with('.') => {
+                let segments: Vec<String> = path[1..].split('.').map(str::to_string).collect();
+                if segments.iter().any(String::is_empty) {
+                    Err(format!("invalid predicate path '{path}'"))
+                } else {
+                    Ok(WatchPredicate::Path(segments))
+                }
+            }
+            other => Err(format!(
+                "unknown predicate '{other}', expected generation, labels, annotations or a .json.path"
+            )),
+        }
+    }
+}
+
+impl WatchPredicate {
+    fn hash_property(&self, obj: &DynamicObject, value: &Value) -> Option<u64> {
+        match self {
+            WatchPredicate::Generation => generation(obj),
+            WatchPredicate::Labels => labels(obj),
+            WatchPredicate::Annotations => annotations(obj),
+            WatchPredicate::Path(segments) => segments
+                .iter()
+                .try_fold(value, |value, segment| match value {
+                    Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+                    _ => value.get(segment),
+                })
+                .map(|found| hash(&found.to_string())),
+        }
+    }
+}
+
+pub fn parse_predicates(predicates: &[String]) -> Result<Vec<WatchPredicate>, String> {
+    predicates.iter().map(|p| p.parse()).collect()
+}
+
+pub(crate) fn predicate_errors(predicates: &[String]) -> Vec<String> {
+    predicates
+        .iter()
+        .filter_map(|p| p.parse::<WatchPredicate>().err())
+        .collect()
+}
+
+/// Remembers the predicate values last seen per object and lets an object
+/// through only when one of them changed. Objects for which no predicate
+/// applies always pass, matching kube-runtime's `predicate_filter`.
+pub struct PredicateCache {
+    predicates: Vec<WatchPredicate>,
+    seen: HashMap<String, u64>,
+}
+
+impl PredicateCache {
+    pub fn new(predicates: Vec<WatchPredicate>) -> Self {
+        PredicateCache {
+            predicates,
+            seen: HashMap::new(),
+        }
+    }
+
+    pub fn changed(&mut self, obj: &DynamicObject) -> bool {
+        if self.predicates.is_empty() {
+            return true;
+        }
+
+        let value = serde_json::to_value(obj).unwrap_or_default();
+        let properties: Vec<Option<u64>> = self
+            .predicates
+            .iter()
+            .map(|p| p.hash_property(obj, &value))
+            .collect();
+        if properties.iter().all(Option::is_none) {
+            return true;
+        }
+
+        // Keyed by uid so a recreated object is always reconciled
+        let key = obj.uid().unwrap_or_else(|| {
+            format!("{}/{}", obj.namespace().unwrap_or_default(), obj.name_any())
+        });
+        let current = hash(&properties);
+        self.seen.insert(key, current) != Some(current)
+    }
+
+    pub fn forget(&mut self, obj: &DynamicObject) {
+        if let Some(uid) = obj.uid() {
+            self.seen.remove(&uid);
+        }
+    }
+}
+
+fn hash<T: Hash>(value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
@@ -0,0 +1,78 @@
+use kube::api::DynamicObject;
+use serde_json::json;
+
+use super::predicate::{PredicateCache, WatchPredicate, parse_predicates, predicate_errors};
+
+fn create_object(generation: Option<i64>, replicas: u32, status: &str) -> DynamicObject {
+    serde_json::from_value(json!({
+        "apiVersion": "apps/v1",
+        "kind": "Deployment",
+        "metadata": {
+            "name": "web",
+            "namespace": "default",
+            "uid": "uid-1",
+            "generation": generation,
+            "labels": { "app": "web" }
+        },
+        "spec": { "replicas": replicas, "ports": [80, 443] },
+        "status": { "phase": status }
+    }))
+    .unwrap()
+}
+
+fn predicates(names: &[&str]) -> Vec<WatchPredicate> {
+    parse_predicates(&names.iter().map(|n| n.to_string()).collect::<Vec<_>>()).unwrap()
+}
+
+#[test]
+fn test_parse_predicates() {
+    assert_eq!(
+        predicates(&["generation", "labels", "annotations", ".spec.ports.1"]),
+        vec![
+            WatchPredicate::Generation,
+            WatchPredicate::Labels,
+            WatchPredicate::Annotations,
+            WatchPredicate::Path(vec!["spec".into(), "ports".into(), "1".into()]),
+        ]
+    );
+
+    let errors = predicate_errors(&["spec".to_string(), ".spec..x".to_string()]);
+    assert_eq!(errors.len(), 2);
+    assert!(errors[0].contains("unknown predicate 'spec'"));
+    assert!(errors[1].contains("invalid predicate path"));
+}
+
+#[test]
+fn test_generation_predicate_ignores_status_updates() {
+    let mut cache = PredicateCache::new(predicates(&["generation"]));
+
+    assert!(cache.changed(&create_object(Some(1), 3, "Pending")));
+    assert!(!cache.changed(&create_object(Some(1), 3, "Running")));
+    assert!(cache.changed(&create_object(Some(2), 4, "Running")));
+
+    // Objects without a generation cannot be filtered
+    assert!(cache.changed(&create_object(None, 4, "Running")));
+    assert!(cache.changed(&create_object(None, 4, "Running")));
+}
+
+#[test]
+fn test_path_predicate_tracks_selected_value() {
+    let mut cache = PredicateCache::new(predicates(&[".spec.replicas"]));
+
+    assert!(cache.changed(&create_object(Some(1), 3, "Pending")));
+    assert!(!cache.changed(&create_object(Some(2), 3, "Running")));
+    assert!(cache.changed(&create_object(Some(3), 5, "Running")));
+
+    // Forgotten objects pass again, e.g. after being deleted and recreated
+    let obj = create_object(Some(3), 5, "Running");
+    cache.forget(&obj);
+    assert!(cache.changed(&obj));
+}
+
+#[test]
+fn test_no_predicates_pass_everything() {
+    let mut cache = PredicateCache::new(vec![]);
+    let obj = create_object(Some(1), 3, "Pending");
+    assert!(cache.changed(&obj));
+    assert!(cache.changed(&obj));
+}
@@ -0,0 +1,161 @@
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use k8s_openapi::chrono::Utc;
+use kube::api::DynamicObject;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::nuop::config::{get_record_dir, get_record_limit};
+
+use super::{
+    redaction::Redactor,
+    state::{CommandResult, InvocationContext},
+};
+
+/// Distinguishes recordings made within the same millisecond.
+static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+/// One script invocation as written by the [`Recorder`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Recording {
+    pub recorded_at: String,
+    pub script: PathBuf,
+    pub command: String,
+    pub context: InvocationContext,
+    /// Environment handed to the script, excluding the temp dir
+    pub env: BTreeMap<String, String>,
+    /// The object passed on stdin
+    pub object: Value,
+    pub exit_code: i32,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Writes every invocation to a directory, keeping the newest `limit`
+/// recordings. The object, lookups, params, environment and output are
+/// redacted first.
+#[derive(Clone, Debug)]
+pub struct Recorder {
+    dir: PathBuf,
+    limit: usize,
+}
+
+impl Recorder {
+    pub fn new(dir: impl Into<PathBuf>, limit: usize) -> Self {
+        Self {
+            dir: dir.into(),
+            limit,
+        }
+    }
+
+    /// Enabled by `NUOP_RECORD_DIR`, bounded by `NUOP_RECORD_LIMIT`.
+    pub fn from_env() -> Option<Self> {
+        let limit = get_record_limit();
+        get_record_dir()
+            .filter(|_| limit > 0)
+            .map(|dir| Self::new(dir, limit))
+    }
+
+    /// Redacts the invocation and writes it off the async runtime, returning
+    /// the path of the recording.
+    pub async fn record(
+        &self,
+        script: &Path,
+        command: &str,
+        obj: &DynamicObject,
+        context: &InvocationContext,
+        result: &CommandResult,
+        redactor: &Redactor,
+    ) -> io::Result<PathBuf> {
+        let object = redactor.redact_object(obj);
+        let mut context = context.clone();
+        if let Some(lookups) = context.lookups.as_mut() {
+            redactor.redact(lookups);
+        }
+        context
+            .params
+            .values_mut()
+            .for_each(|v| *v = redactor.mask(v));
+        // Params sourced from Secrets reach the script as environment too
+        let env = context
+            .env_vars()
+            .into_iter()
+            .map(|(k, v)| (k, redactor.mask(&v)))
+            .collect();
+
+        let recording = Recording {
+            recorded_at: Utc::now().to_rfc3339(),
+            script: script.to_path_buf(),
+            command: command.to_string(),
+            env,
+            context,
+            object,
+            exit_code: result.exit_code,
+            stdout: redactor.redact_output(&result.stdout),
+            stderr: redactor.mask(&result.stderr),
+        };
+
+        let file_name = format!(
+            "{:013}-{:06}-{}.yaml",
+            Utc::now().timestamp_millis(),
+            SEQUENCE.fetch_add(1, Ordering::Relaxed) % 1_000_000,
+            sanitize(&recording.context.script_name)
+        );
+        let path = self.dir.join(file_name);
+        let yaml = serde_yaml::to_string(&recording).map_err(io::Error::other)?;
+
+        let recorder = self.clone();
+        tokio::task::spawn_blocking(move || recorder.write(path, yaml))
+            .await
+            .map_err(io::Error::other)?
+    }
+
+    fn write(&self, path: PathBuf, yaml: String) -> io::Result<PathBuf> {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(&path, yaml)?;
+        self.prune()?;
+        Ok(path)
+    }
+
+    /// Removes the oldest recordings beyond the limit. File names start with
+    /// the recording time, so they sort oldest first.
+    fn prune(&self) -> io::Result<()> {
+        let mut recordings: Vec<_> = fs::read_dir(&self.dir)?
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension() == Some("yaml".as_ref()))
+            .collect();
+        recordings.sort();
+
+        let excess = recordings.len().saturating_sub(self.limit);
+        for path in &recordings[..excess] {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn load_recording(path: &Path) -> anyhow::Result<Recording> {
+    use anyhow::Context;
+
+    let content = fs::read_to_string(path).with_context(|| format!("Failed to read {path:?}"))?;
+    serde_yaml::from_str(&content).with_context(|| format!("Failed to parse recording {path:?}"))
+}
+
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,117 @@
+use std::fs;
+
+use kube::api::DynamicObject;
+use serde_json::json;
+
+use super::{
+    config::Config,
+    recorder::{Recorder, load_recording},
+    redaction::{REDACTED, Redactor},
+    state::{CommandResult, InvocationContext},
+};
+
+fn secret() -> serde_json::Value {
+    json!({
+        "apiVersion": "v1",
+        "kind": "Secret",
+        "metadata": {
+            "name": "creds",
+            "namespace": "default",
+            "annotations": {
+                "kubectl.kubernetes.io/last-applied-configuration": "{\"data\":{\"password\":\"aHVudGVyMg==\"}}",
+                "team": "payments"
+            }
+        },
+        "data": { "password": "aHVudGVyMg==" },
+        "stringData": { "token": "hunter2" }
+    })
+}
+
+fn config() -> Config {
+    serde_yaml::from_str("name: secret-cloner\nversion: v1\nkind: Secret\n").unwrap()
+}
+
+fn result(exit_code: i32, stdout: &str) -> CommandResult {
+    CommandResult {
+        exit_code,
+        stdout: stdout.to_string(),
+        stderr: String::new(),
+    }
+}
+
+#[tokio::test]
+async fn test_recorder_keeps_newest_recordings() {
+    let dir = tempfile::tempdir().unwrap();
+    let recorder = Recorder::new(dir.path(), 2);
+    let obj: DynamicObject = serde_json::from_value(secret()).unwrap();
+    let mut context = InvocationContext::new(&config(), &obj, None, 1);
+    context.lookups = Some(json!({ "creds": [secret()] }));
+
+    let mut paths = Vec::new();
+    for code in 0..3 {
+        let path = recorder
+            .record(
+                "mod.nu".as_ref(),
+                "reconcile",
+                &obj,
+                &context,
+                &result(code, "done"),
+                &Redactor::default(),
+            )
+            .await
+            .unwrap();
+        paths.push(path);
+    }
+
+    let mut remaining: Vec<_> = fs::read_dir(dir.path())
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .collect();
+    remaining.sort();
+    assert_eq!(remaining, paths[1..]);
+
+    let recording = load_recording(&paths[2]).unwrap();
+    assert_eq!(recording.command, "reconcile");
+    assert_eq!(recording.exit_code, 2);
+    assert_eq!(recording.stdout, "done");
+    assert_eq!(recording.object["data"]["password"], REDACTED);
+    assert_eq!(
+        recording.context.lookups.unwrap()["creds"][0]["stringData"]["token"],
+        REDACTED
+    );
+    assert_eq!(recording.env["NUOP_SCRIPT_NAME"], "secret-cloner");
+}
+
+#[tokio::test]
+async fn test_recorder_masks_secret_params() {
+    let dir = tempfile::tempdir().unwrap();
+    let recorder = Recorder::new(dir.path(), 1);
+    let obj: DynamicObject = serde_json::from_value(secret()).unwrap();
+    let mut context = InvocationContext::new(&config(), &obj, None, 1);
+    context
+        .params
+        .insert("API_TOKEN".to_string(), "s3cr3t-token".to_string());
+    context
+        .params
+        .insert("REGION".to_string(), "eu-west-1".to_string());
+    let redactor = Redactor::new(Vec::new(), vec!["s3cr3t-token".to_string()]);
+
+    let path = recorder
+        .record(
+            "mod.nu".as_ref(),
+            "reconcile",
+            &obj,
+            &context,
+            &result(0, ""),
+            &redactor,
+        )
+        .await
+        .unwrap();
+
+    let yaml = fs::read_to_string(&path).unwrap();
+    assert!(!yaml.contains("s3cr3t-token"));
+    let recording = load_recording(&path).unwrap();
+    assert_eq!(recording.context.params["API_TOKEN"], REDACTED);
+    assert_eq!(recording.env["API_TOKEN"], REDACTED);
+    assert_eq!(recording.env["REGION"], "eu-west-1");
+}
@@ -0,0 +1,203 @@
+use std::{fs, path::Path};
+
+use k8s_openapi::ByteString;
+use kube::api::DynamicObject;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::nuop::config::{get_redact_paths, get_secrets_path};
+
+/// Replaces redacted values in logs and recordings.
+pub const REDACTED: &str = "<redacted>";
+
+const LAST_APPLIED: &str = "kubectl.kubernetes.io/last-applied-configuration";
+
+/// Shorter values are not masked, they would garble unrelated output.
+const MIN_SECRET_LEN: usize = 4;
+
+/// Removes secrets from objects and script output before they are logged or
+/// recorded: the `data` and `stringData` of Secrets, the configured field
+/// paths and any occurrence of a known secret value.
+#[derive(Clone, Debug, Default)]
+pub struct Redactor {
+    /// Dot separated field paths, `*` matches any key or list item
+    paths: Vec<Vec<String>>,
+    /// Known secret values, longest first so overlapping values are fully
+    /// masked
+    values: Vec<String>,
+}
+
+impl Redactor {
+    pub fn new<P, V>(paths: P, values: V) -> Self
+    where
+        P: IntoIterator<Item = String>,
+        V: IntoIterator<Item = String>,
+    {
+        let paths = paths
+            .into_iter()
+            .filter(|p| !p.is_empty())
+            .map(|p| p.split('.').map(str::to_string).collect())
+            .collect();
+
+        let mut values: Vec<String> = values
+            .into_iter()
+            .map(|v| v.trim().to_string())
+            .filter(|v| v.len() >= MIN_SECRET_LEN)
+            // Secret manifests carry the base64 encoding of the value
+            .flat_map(|v| {
+                let encoded = serde_json::to_value(ByteString(v.as_bytes().to_vec()))
+                    .ok()
+                    .and_then(|e| e.as_str().map(str::to_string));
+                std::iter::once(v).chain(encoded)
+            })
+            .collect();
+        values.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+        values.dedup();
+
+        Self { paths, values }
+    }
+
+    /// Redacts the paths in `NUOP_REDACT_PATHS` and masks the values of the
+    /// credentials mounted below `NUOP_SECRETS_PATH`, as well as the given
+    /// values, e.g. params sourced from Secrets.
+    pub fn from_env(secret_values: impl IntoIterator<Item = String>) -> Self {
+        let mut values: Vec<String> = secret_values.into_iter().collect();
+        read_secret_values(&get_secrets_path(), &mut values);
+        Self::new(get_redact_paths(), values)
+    }
+
+    /// The object as a JSON value with secrets redacted.
+    pub fn redact_object(&self, obj: &DynamicObject) -> Value {
+        let mut value = serde_json::to_value(obj).unwrap_or_default();
+        self.redact(&mut value);
+        value
+    }
+
+    /// Redacts Secrets found anywhere in the value, including Secrets nested
+    /// in lists and lookups, and the configured paths from its root.
+    pub fn redact(&self, value: &mut Value) {
+        redact_secrets(value);
+        for path in &self.paths {
+            redact_path(value, path);
+        }
+        self.mask_strings(value);
+    }
+
+    /// Replaces every known secret value in the text.
+    pub fn mask(&self, text: &str) -> String {
+        let mut masked = text.to_string();
+        for value in &self.values {
+            if masked.contains(value.as_str()) {
+                masked = masked.replace(value.as_str(), REDACTED);
+            }
+        }
+        masked
+    }
+
+    /// Redacts Secrets in script output holding manifests and masks known
+    /// secret values. Other output is only masked.
+    pub fn redact_output(&self, stdout: &str) -> String {
+        let documents: Result<Vec<Value>, _> = serde_yaml::Deserializer::from_str(stdout)
+            .map(Value::deserialize)
+            .collect();
+        let Ok(mut documents) = documents else {
+            return self.mask(stdout);
+        };
+
+        let original = documents.clone();
+        documents.iter_mut().for_each(redact_secrets);
+        if documents == original {
+            return self.mask(stdout);
+        }
+
+        let redacted = documents
+            .iter()
+            .filter_map(|document| serde_yaml::to_string(document).ok())
+            .collect::<Vec<_>>()
+            .join("---\n");
+        self.mask(&redacted)
+    }
+
+    fn mask_strings(&self, value: &mut Value) {
+        if self.values.is_empty() {
+            return;
+        }
+        match value {
+            Value::String(s) => *s = self.mask(s),
+            Value::Object(fields) => fields.values_mut().for_each(|v| self.mask_strings(v)),
+            Value::Array(items) => items.iter_mut().for_each(|v| self.mask_strings(v)),
+            _ => {}
+        }
+    }
+}
+
+fn redact_secrets(value: &mut Value) {
+    match value {
+        Value::Object(fields) => {
+            let is_secret = fields.get("kind") == Some(&Value::from("Secret"))
+                && fields.get("apiVersion") == Some(&Value::from("v1"));
+            if is_secret {
+                for key in ["data", "stringData"] {
+                    if let Some(Value::Object(data)) = fields.get_mut(key) {
+                        data.values_mut().for_each(|v| *v = Value::from(REDACTED));
+                    }
+                }
+                if let Some(Value::Object(annotations)) = fields
+                    .get_mut("metadata")
+                    .and_then(|m| m.get_mut("annotations"))
+                    && let Some(last_applied) = annotations.get_mut(LAST_APPLIED)
+                {
+                    *last_applied = Value::from(REDACTED);
+                }
+            }
+            fields.values_mut().for_each(redact_secrets);
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact_secrets),
+        _ => {}
+    }
+}
+
+fn redact_path(value: &mut Value, path: &[String]) {
+    let Some((key, rest)) = path.split_first() else {
+        *value = Value::from(REDACTED);
+        return;
+    };
+    match value {
+        Value::Object(fields) if key == "*" => {
+            fields.values_mut().for_each(|v| redact_path(v, rest))
+        }
+        Value::Object(fields) => {
+            if let Some(v) = fields.get_mut(key) {
+                redact_path(v, rest)
+            }
+        }
+        Value::Array(items) if key == "*" => items.iter_mut().for_each(|v| redact_path(v, rest)),
+        Value::Array(items) => {
+            if let Some(v) = key.parse::<usize>().ok().and_then(|i| items.get_mut(i)) {
+                redact_path(v, rest)
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Collects the contents of the files below a mounted secret volume,
+/// skipping the `..data` links kubelet maintains next to the keys.
+pub(crate) fn read_secret_values(dir: &Path, values: &mut Vec<String>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for path in entries.flatten().map(|e| e.path()) {
+        if path
+            .file_name()
+            .is_some_and(|n| n.to_string_lossy().starts_with(".."))
+        {
+            continue;
+        }
+        if path.is_dir() {
+            read_secret_values(&path, values);
+        } else if let Ok(content) = fs::read_to_string(&path) {
+            values.push(content);
+        }
+    }
+}
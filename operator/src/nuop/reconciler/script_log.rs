@@ -0,0 +1,111 @@
+use serde_json::{Map, Value};
+use tracing::{Level, Span, debug, error, info, info_span, trace, warn};
+
+use super::config::Config;
+
+const LEVEL_KEYS: [&str; 3] = ["level", "lvl", "severity"];
+const MESSAGE_KEYS: [&str; 2] = ["msg", "message"];
+
+/// A line of script output that is a JSON log record.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ScriptLine {
+    pub level: Level,
+    pub message: String,
+    /// The remaining keys of the record
+    pub fields: Map<String, Value>,
+}
+
+macro_rules! event_at {
+    ($level:expr, $($arg:tt)+) => {
+        match $level {
+            Level::ERROR => error!($($arg)+),
+            Level::WARN => warn!($($arg)+),
+            Level::INFO => info!($($arg)+),
+            Level::DEBUG => debug!($($arg)+),
+            _ => trace!($($arg)+),
+        }
+    };
+}
+
+/// The span wrapping every script invocation for an object, so log lines
+/// can be attributed to the script and object that produced them.
+pub(crate) fn reconcile_span(
+    config: &Config,
+    namespace: Option<&str>,
+    name: &str,
+    attempt: u32,
+) -> Span {
+    info_span!(
+        "reconcile",
+        script = %config.name,
+        group = %config.group,
+        version = %config.version,
+        kind = %config.kind,
+        namespace = namespace.unwrap_or_default(),
+        name,
+        attempt,
+    )
+}
+
+/// Parses a line that is a JSON object with a level or message key, such as
+/// `{"level": "warn", "msg": "quota low", "used": 9}`. Unknown levels fall
+/// back to `default`.
+pub(crate) fn parse_line(line: &str, default: Level) -> Option<ScriptLine> {
+    let line = line.trim();
+    if !line.starts_with('{') {
+        return None;
+    }
+    let Ok(Value::Object(mut fields)) = serde_json::from_str(line) else {
+        return None;
+    };
+
+    let level = take_string(&mut fields, &LEVEL_KEYS);
+    let message = take_string(&mut fields, &MESSAGE_KEYS);
+    if level.is_none() && message.is_none() {
+        return None;
+    }
+
+    Some(ScriptLine {
+        level: level.as_deref().and_then(parse_level).unwrap_or(default),
+        message: message.unwrap_or_default(),
+        fields,
+    })
+}
+
+/// Re-emits script output line by line. JSON log records keep their level
+/// and fields, other lines are logged at `default` prefixed with the stream.
+pub(crate) fn log_output(stream: &str, output: &str, default: Level, structured: bool) {
+    for line in output.lines() {
+        match parse_line(line, default).filter(|_| structured) {
+            Some(record) if record.fields.is_empty() => {
+                event_at!(record.level, stream, "{}", record.message)
+            }
+            Some(record) => {
+                // Merged into the event's fields by the JSON log format
+                let fields = Value::Object(record.fields);
+                event_at!(record.level, stream, fields = %fields, "{}", record.message)
+            }
+            None => event_at!(default, "{}: {}", stream, line),
+        }
+    }
+}
+
+fn take_string(fields: &mut Map<String, Value>, keys: &[&str]) -> Option<String> {
+    keys.iter()
+        .find_map(|key| fields.remove(*key))
+        .map(|value| match value {
+            Value::String(s) => s,
+            other => other.to_string(),
+        })
+}
+
+fn parse_level(level: &str) -> Option<Level> {
+    match level.to_ascii_lowercase().as_str() {
+        "fatal" | "critical" | "error" | "err" => Some(Level::ERROR),
+        "warning" | "warn" => Some(Level::WARN),
+        "info" | "notice" => Some(Level::INFO),
+        "debug" => Some(Level::DEBUG),
+        "trace" => Some(Level::TRACE),
+        _ => None,
+    }
+}
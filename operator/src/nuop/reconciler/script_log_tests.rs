@@ -0,0 +1,51 @@
+use serde_json::json;
+use tracing::Level;
+
+use super::script_log::{ScriptLine, parse_line};
+
+#[test]
+fn test_parse_json_log_line() {
+    let line = parse_line(
+        r#"{"level": "WARNING", "msg": "quota low", "used": 9, "tags": ["a"]}"#,
+        Level::INFO,
+    )
+    .unwrap();
+
+    assert_eq!(
+        line,
+        ScriptLine {
+            level: Level::WARN,
+            message: "quota low".to_string(),
+            fields: json!({ "used": 9, "tags": ["a"] })
+                .as_object()
+                .unwrap()
+                .clone(),
+        }
+    );
+}
+
+#[test]
+fn test_parse_line_level_aliases_and_defaults() {
+    let line = parse_line(r#"{"severity": "fatal", "message": "boom"}"#, Level::INFO).unwrap();
+    assert_eq!(line.level, Level::ERROR);
+    assert_eq!(line.message, "boom");
+
+    let line = parse_line(r#"{"lvl": "verbose", "msg": "x"}"#, Level::INFO).unwrap();
+    assert_eq!(line.level, Level::INFO);
+
+    let line = parse_line(r#"{"msg": "no level"}"#, Level::ERROR).unwrap();
+    assert_eq!(line.level, Level::ERROR);
+
+    let line = parse_line(r#"{"level": "debug"}"#, Level::INFO).unwrap();
+    assert_eq!(line.level, Level::DEBUG);
+    assert_eq!(line.message, "");
+}
+
+#[test]
+fn test_plain_and_result_lines_are_not_log_records() {
+    assert_eq!(parse_line("Reconciling web", Level::INFO), None);
+    assert_eq!(parse_line("{not json", Level::INFO), None);
+    assert_eq!(parse_line(r#"["a", "b"]"#, Level::INFO), None);
+    // Structured results of imperative scripts carry no level or message
+    assert_eq!(parse_line(r#"{"changed": true}"#, Level::INFO), None);
+}
@@ -1,12 +1,15 @@
-use super::{controller::controller as reconciler_controller, util::get_script_config};
+use super::{
+    controller::controller as reconciler_controller, dispatch::group_by_kind,
+    util::get_script_config,
+};
 use kube::Client;
-use std::{collections::HashSet, path::PathBuf};
+use std::path::PathBuf;
 use tokio::task::JoinHandle;
 use tracing::error;
 
+/// Spawns one controller per watched kind, shared by all scripts of that kind.
 pub fn get_standard_controllers(client: &Client, scripts: &[PathBuf]) -> Vec<JoinHandle<()>> {
-    let mut processed_kinds = HashSet::new();
-    scripts
+    let configs = scripts
         .iter()
         .filter_map(|script| {
             get_script_config(script)
@@ -14,24 +17,18 @@ pub fn get_standard_controllers(client: &Client, scripts: &[PathBuf]) -> Vec<Joi
                     error!("Failed to get script config for {:?}: {:?}", script, e);
                 })
                 .ok()
-                .and_then(|config| {
-                    if processed_kinds.insert(config.kind.clone()) {
-                        Some((script, config))
-                    } else {
-                        error!(
-                            "Duplicate kind found for script {:?}: {:?}",
-                            &script, &config.kind
-                        );
-                        None
-                    }
-                })
+                .map(|config| (script, config))
         })
-        .map(|(script, config)| {
-            tokio::spawn(reconciler_controller(
-                client.clone(),
-                config,
-                script.clone(),
-            ))
+        .flat_map(|(script, config)| {
+            config
+                .watched()
+                .into_iter()
+                .map(move |config| (script.clone(), config))
         })
+        .collect();
+
+    group_by_kind(configs)
+        .into_iter()
+        .map(|group| tokio::spawn(reconciler_controller(client.clone(), group)))
         .collect()
 }
@@ -55,7 +55,7 @@ async fn test_with_duplicate_kinds() {
 
     let controllers = get_standard_controllers(&client, &scripts);
 
-    // Should only create 1 controller (duplicate Pod kind should be rejected)
+    // Should only create 1 controller (both Pod scripts share the Pod watch)
     assert_eq!(controllers.len(), 1);
 
     // Clean up
@@ -156,7 +156,7 @@ async fn test_multiple_duplicates_same_kind() {
 
     let controllers = get_standard_controllers(&client, &scripts);
 
-    // Should create 2 controllers (first Pod and Deployment, second Pod should be rejected)
+    // Should create 2 controllers (Pod, shared by both Pod scripts, and Deployment)
     assert_eq!(controllers.len(), 2);
 
     // Clean up
@@ -222,13 +222,13 @@ async fn test_kind_deduplication_logic() {
     let scripts = vec![
         PathBuf::from("src/nuop/reconciler/standard_tests/scripts/pod-controller/mod.nu"), // Pod - should be accepted (first)
         PathBuf::from("src/nuop/reconciler/standard_tests/scripts/deployment-controller/mod.nu"), // Deployment - should be accepted
-        PathBuf::from("src/nuop/reconciler/standard_tests/scripts/duplicate-pod-controller/mod.nu"), // Pod - should be rejected (duplicate)
+        PathBuf::from("src/nuop/reconciler/standard_tests/scripts/duplicate-pod-controller/mod.nu"), // Pod - shares the Pod controller
         PathBuf::from("src/nuop/reconciler/standard_tests/scripts/service-controller/mod.nu"), // Service - should be accepted
     ];
 
     let controllers = get_standard_controllers(&client, &scripts);
 
-    // Should create 3 controllers (Pod, Deployment, Service - duplicate Pod rejected)
+    // Should create 3 controllers (Pod, Deployment, Service - one per kind)
     assert_eq!(controllers.len(), 3);
 
     // Clean up
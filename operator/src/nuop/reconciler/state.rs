@@ -1,9 +1,38 @@
 use async_trait::async_trait;
-use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    env,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
 
-use kube::{Client, api::ApiResource};
+use kube::{
+    Client, ResourceExt,
+    api::{ApiResource, DynamicObject, GroupVersionKind},
+    discovery::{self, ApiCapabilities},
+    runtime::reflector::Store,
+};
+use tokio::sync::OnceCell;
+use tracing::error;
 
-use super::config::Config;
+use crate::nuop::{
+    constants::{
+        NUOP_ATTEMPT, NUOP_DRY_RUN, NUOP_ENV_ALLOWLIST, NUOP_FINALIZER, NUOP_GROUP, NUOP_KIND,
+        NUOP_LOOKUPS, NUOP_OBJECT_NAME, NUOP_OBJECT_NAMESPACE, NUOP_OBJECT_UID, NUOP_POD_NAMESPACE,
+        NUOP_SCRIPT_NAME, NUOP_TMPDIR, NUOP_VERSION,
+    },
+    telemetry::trace_env,
+};
+
+use super::{
+    config::{Config, Lookup},
+    filter::Filter,
+    fingerprint::script_hash,
+    recorder::Recorder,
+    redaction::Redactor,
+    util::pod_namespace,
+};
 
 // Command execution abstraction following DIP (Dependency Inversion Principle)
 #[async_trait]
@@ -13,9 +42,81 @@ pub trait CommandExecutor: Clone + Send + Sync + 'static {
         script: &Path,
         command: &str,
         input: &str,
+        context: &InvocationContext,
     ) -> Result<CommandResult, anyhow::Error>;
 }
 
+// Runtime details handed to every script invocation
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct InvocationContext {
+    pub script_name: String,
+    pub gvk: GroupVersionKind,
+    pub namespace: Option<String>,
+    pub name: String,
+    pub uid: Option<String>,
+    pub pod_namespace: Option<String>,
+    pub attempt: u32,
+    pub dry_run: bool,
+    pub finalizer: Option<String>,
+    pub params: BTreeMap<String, String>,
+    /// Cached lookup objects, written to a file named by `NUOP_LOOKUPS`
+    pub lookups: Option<serde_json::Value>,
+}
+
+impl InvocationContext {
+    pub fn new(
+        config: &Config,
+        obj: &DynamicObject,
+        pod_namespace: Option<String>,
+        attempt: u32,
+    ) -> Self {
+        InvocationContext {
+            script_name: config.name.clone(),
+            gvk: config.into(),
+            namespace: obj.namespace(),
+            name: obj.name_any(),
+            uid: obj.uid(),
+            pod_namespace,
+            attempt,
+            dry_run: config.dry_run,
+            finalizer: None,
+            params: config.params.clone(),
+            lookups: None,
+        }
+    }
+
+    /// Environment variables describing this invocation, excluding the temp dir
+    /// which is only known to the executor. Parameters come first so they
+    /// cannot shadow the standard variables.
+    pub fn env_vars(&self) -> Vec<(String, String)> {
+        let standard = [
+            (NUOP_SCRIPT_NAME, self.script_name.clone()),
+            (NUOP_GROUP, self.gvk.group.clone()),
+            (NUOP_VERSION, self.gvk.version.clone()),
+            (NUOP_KIND, self.gvk.kind.clone()),
+            (
+                NUOP_OBJECT_NAMESPACE,
+                self.namespace.clone().unwrap_or_default(),
+            ),
+            (NUOP_OBJECT_NAME, self.name.clone()),
+            (NUOP_OBJECT_UID, self.uid.clone().unwrap_or_default()),
+            (
+                NUOP_POD_NAMESPACE,
+                self.pod_namespace.clone().unwrap_or_default(),
+            ),
+            (NUOP_ATTEMPT, self.attempt.to_string()),
+            (NUOP_DRY_RUN, self.dry_run.to_string()),
+            (NUOP_FINALIZER, self.finalizer.clone().unwrap_or_default()),
+        ];
+
+        self.params
+            .clone()
+            .into_iter()
+            .chain(standard.map(|(k, v)| (k.to_string(), v)))
+            .collect()
+    }
+}
+
 #[derive(Debug)]
 pub struct CommandResult {
     pub exit_code: i32,
@@ -23,9 +124,47 @@ pub struct CommandResult {
     pub stderr: String,
 }
 
+/// Variables copied from the operator even when `NUOP_ENV_ALLOWLIST` is
+/// set, since `nu` needs them to find its config and data directories.
+pub(crate) const INHERITED_ENV: [&str; 7] = [
+    "PATH",
+    "HOME",
+    "XDG_CONFIG_HOME",
+    "XDG_DATA_HOME",
+    "XDG_CACHE_HOME",
+    "XDG_STATE_HOME",
+    "XDG_RUNTIME_DIR",
+];
+
 // Default implementation using actual process execution
 #[derive(Clone, Debug, Default)]
-pub struct ProcessExecutor;
+pub struct ProcessExecutor {
+    /// When set, scripts start from an empty environment and only these
+    /// variables (plus [`INHERITED_ENV`]) are copied over from the operator.
+    pub env_allowlist: Option<Vec<String>>,
+}
+
+impl ProcessExecutor {
+    pub fn from_env() -> Self {
+        let env_allowlist = env::var(NUOP_ENV_ALLOWLIST).ok().map(|v| {
+            v.split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .collect()
+        });
+        Self { env_allowlist }
+    }
+
+    /// The operator's variables passed to scripts under the allowlist.
+    pub(crate) fn allowed_env(allowlist: &[String]) -> Vec<(String, String)> {
+        allowlist
+            .iter()
+            .map(String::as_str)
+            .chain(INHERITED_ENV)
+            .filter_map(|key| env::var(key).ok().map(|value| (key.to_string(), value)))
+            .collect()
+    }
+}
 
 #[async_trait]
 impl CommandExecutor for ProcessExecutor {
@@ -34,6 +173,7 @@ impl CommandExecutor for ProcessExecutor {
         script: &Path,
         command: &str,
         input: &str,
+        context: &InvocationContext,
     ) -> Result<CommandResult, anyhow::Error> {
         use std::io::{BufRead, BufReader, Write};
         use std::process::{Command, Stdio};
@@ -42,9 +182,32 @@ impl CommandExecutor for ProcessExecutor {
         let script = script.to_path_buf();
         let command = command.to_string();
         let input = input.to_string();
+        let env_vars = context.env_vars();
+        let env_allowlist = self.env_allowlist.clone();
+        let lookups = context.lookups.clone();
+        let trace_env = trace_env();
 
         task::spawn_blocking(move || {
-            let mut child = Command::new("nu")
+            // Removed again when dropped at the end of this invocation
+            let tmp_dir = tempfile::Builder::new().prefix("nuop-").tempdir()?;
+
+            let mut cmd = Command::new("nu");
+            if let Some(allowlist) = env_allowlist {
+                cmd.env_clear()
+                    .envs(ProcessExecutor::allowed_env(&allowlist));
+            }
+
+            if let Some(lookups) = lookups {
+                let path = tmp_dir.path().join("lookups.json");
+                std::fs::write(&path, serde_json::to_vec(&lookups)?)?;
+                cmd.env(NUOP_LOOKUPS, path);
+            }
+
+            let mut child = cmd
+                .envs(env_vars)
+                .envs(trace_env)
+                .env(NUOP_TMPDIR, tmp_dir.path())
+                .env("TMPDIR", tmp_dir.path())
                 .arg("--stdin")
                 .arg(&script)
                 .arg(&command)
@@ -112,13 +275,29 @@ where
     pub config: Config,
     pub script: PathBuf,
     pub executor: E,
+    pub pod_namespace: Option<String>,
+    /// Hash of the script's files, computed when first needed
+    script_hash: OnceCell<String>,
+    /// The compiled `filter`, checked when the controller starts
+    pub filter: Option<Filter>,
+    /// Finalizers of higher-priority scripts on the same kind, removed before
+    /// this script runs `finalize`
+    pub finalize_after: Vec<String>,
+    /// Shared caches of the config's `lookups`, see [`lookup_store`](super::lookup::lookup_store)
+    pub lookups: Vec<(Lookup, Store<DynamicObject>)>,
+    /// Records invocations when `NUOP_RECORD_DIR` is set
+    pub recorder: Option<Recorder>,
+    /// Applied to objects and script output before they are logged or recorded
+    pub redactor: Redactor,
+    /// Consecutive attempts per object, and whether it was being deleted
+    attempts: Arc<Mutex<HashMap<String, (bool, u32)>>>,
+    resources: Arc<Mutex<HashMap<GroupVersionKind, (ApiResource, ApiCapabilities)>>>,
 }
 
 impl<E> State<E>
 where
     E: CommandExecutor,
 {
-    #[allow(dead_code)] // Used for testing with custom executors
     pub fn new(
         api_resource: ApiResource,
         client: Client,
@@ -126,14 +305,98 @@ where
         script: PathBuf,
         executor: E,
     ) -> Self {
+        let filter = match config.filter.as_deref().map(str::parse::<Filter>) {
+            Some(Ok(filter)) => Some(filter),
+            Some(Err(e)) => {
+                error!("Ignoring invalid filter for {}: {}", config.name, e);
+                None
+            }
+            None => None,
+        };
+        let secret_params = config
+            .secret_params
+            .iter()
+            .filter_map(|name| config.params.get(name).cloned())
+            .collect::<Vec<_>>();
         State {
             api_resource,
             client,
             config,
+            script_hash: OnceCell::new(),
+            filter,
+            finalize_after: Vec::new(),
+            lookups: Vec::new(),
+            recorder: Recorder::from_env(),
+            redactor: Redactor::from_env(secret_params),
             script,
             executor,
+            pod_namespace: pod_namespace(),
+            attempts: Default::default(),
+            resources: Default::default(),
         }
     }
+
+    /// Hash of the script's files, see [`script_hash`]. Read off the runtime
+    /// threads on first use, since scripts may pull in many files.
+    pub async fn script_hash(&self) -> &str {
+        self.script_hash
+            .get_or_init(|| async {
+                let script = self.script.clone();
+                tokio::task::spawn_blocking(move || script_hash(&script))
+                    .await
+                    .unwrap_or_default()
+            })
+            .await
+    }
+
+    /// Resolves and caches the API resource and scope of a kind via discovery.
+    pub async fn resolve_kind(
+        &self,
+        gvk: &GroupVersionKind,
+    ) -> Result<(ApiResource, ApiCapabilities), kube::Error> {
+        if let Some(resolved) = self
+            .resources
+            .lock()
+            .expect("resources lock poisoned")
+            .get(gvk)
+        {
+            return Ok(resolved.clone());
+        }
+
+        let resolved = discovery::pinned_kind(&self.client, gvk).await?;
+        self.resources
+            .lock()
+            .expect("resources lock poisoned")
+            .insert(gvk.clone(), resolved.clone());
+        Ok(resolved)
+    }
+
+    /// Records a reconcile attempt for the given object key and returns the
+    /// number of consecutive attempts since the last successful reconcile.
+    /// Counting restarts once the object is being deleted, so failures from
+    /// before deletion do not use up `finalizeMaxRetries`.
+    pub fn next_attempt(&self, key: &str, deleting: bool) -> u32 {
+        let mut attempts = self.attempts.lock().expect("attempts lock poisoned");
+        let entry = attempts.entry(key.to_string()).or_insert((deleting, 0));
+        if entry.0 != deleting {
+            *entry = (deleting, 0);
+        }
+        entry.1 += 1;
+        entry.1
+    }
+
+    /// Forgets the attempts of an object, on success or once it is deleted.
+    pub fn reset_attempts(&self, key: &str) {
+        self.attempts
+            .lock()
+            .expect("attempts lock poisoned")
+            .remove(key);
+    }
+}
+
+/// The key attempts are counted under, `namespace/name`.
+pub(crate) fn attempt_key(obj: &DynamicObject) -> String {
+    format!("{}/{}", obj.namespace().unwrap_or_default(), obj.name_any())
 }
 
 // Convenience constructor for default case (maintains backward compatibility)
@@ -144,12 +407,12 @@ impl State<ProcessExecutor> {
         config: Config,
         script: PathBuf,
     ) -> Self {
-        State {
+        State::new(
             api_resource,
             client,
             config,
             script,
-            executor: ProcessExecutor,
-        }
+            ProcessExecutor::from_env(),
+        )
     }
 }
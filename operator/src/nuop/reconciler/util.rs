@@ -1,8 +1,21 @@
+use crate::nuop::constants::POD_NAMESPACE;
 use crate::nuop::reconciler::config::Config;
 use anyhow::{Context, Result};
-use std::{path::PathBuf, process::Command};
+use std::{env, fs, path::PathBuf, process::Command};
 use tracing::debug;
 
+const SERVICE_ACCOUNT_NAMESPACE: &str = "/var/run/secrets/kubernetes.io/serviceaccount/namespace";
+
+/// Namespace the operator pod runs in, taken from `POD_NAMESPACE` or the
+/// mounted service account.
+pub fn pod_namespace() -> Option<String> {
+    env::var(POD_NAMESPACE)
+        .ok()
+        .or_else(|| fs::read_to_string(SERVICE_ACCOUNT_NAMESPACE).ok())
+        .map(|ns| ns.trim().to_string())
+        .filter(|ns| !ns.is_empty())
+}
+
 pub fn get_script_config(script: &PathBuf) -> Result<Config> {
     let output = Command::new("nu")
         .arg(script)
@@ -0,0 +1,60 @@
+use std::{collections::HashMap, env};
+
+use opentelemetry::propagation::TextMapPropagator;
+use opentelemetry_otlp::SpanExporter;
+use opentelemetry_sdk::{Resource, propagation::TraceContextPropagator, trace::SdkTracerProvider};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+use super::constants::{
+    OTEL_EXPORTER_OTLP_ENDPOINT, OTEL_EXPORTER_OTLP_TRACES_ENDPOINT, OTEL_SERVICE_NAME,
+    TRACEPARENT, TRACESTATE,
+};
+
+/// Name of the tracer and default service name of exported spans.
+pub const TRACER_NAME: &str = "nuop";
+
+/// Builds an OTLP/gRPC exporter when `OTEL_EXPORTER_OTLP_ENDPOINT` or
+/// `OTEL_EXPORTER_OTLP_TRACES_ENDPOINT` is set. The exporter reads the
+/// remaining standard `OTEL_*` variables itself. Must be called within a
+/// Tokio runtime.
+pub fn tracer_provider() -> anyhow::Result<Option<SdkTracerProvider>> {
+    let enabled = [
+        OTEL_EXPORTER_OTLP_ENDPOINT,
+        OTEL_EXPORTER_OTLP_TRACES_ENDPOINT,
+    ]
+    .iter()
+    .any(|key| env::var(key).is_ok_and(|v| !v.is_empty()));
+    if !enabled {
+        return Ok(None);
+    }
+
+    let exporter = SpanExporter::builder().with_tonic().build()?;
+    let mut resource = Resource::builder();
+    if env::var(OTEL_SERVICE_NAME).is_err() {
+        resource = resource.with_service_name(TRACER_NAME);
+    }
+
+    Ok(Some(
+        SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(resource.build())
+            .build(),
+    ))
+}
+
+/// `TRACEPARENT` and `TRACESTATE` of the current span, for processes that
+/// continue the trace. Empty when the span is not exported.
+pub fn trace_env() -> Vec<(&'static str, String)> {
+    let mut carrier = HashMap::new();
+    TraceContextPropagator::new().inject_context(&tracing::Span::current().context(), &mut carrier);
+
+    carrier
+        .into_iter()
+        .filter(|(_, value)| !value.is_empty())
+        .filter_map(|(key, value)| match key.as_str() {
+            "traceparent" => Some((TRACEPARENT, value)),
+            "tracestate" => Some((TRACESTATE, value)),
+            _ => None,
+        })
+        .collect()
+}
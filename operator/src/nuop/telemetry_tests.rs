@@ -0,0 +1,102 @@
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_sdk::trace::{InMemorySpanExporter, SdkTracerProvider, SpanData};
+use tracing::{info_span, subscriber::DefaultGuard};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+
+use super::{
+    constants::TRACEPARENT,
+    telemetry::{TRACER_NAME, trace_env},
+    testing::{Config, Harness, StubExecutor, object_from_yaml},
+};
+
+/// Exports spans of the current thread to memory, standing in for a
+/// collector.
+fn collector() -> (InMemorySpanExporter, SdkTracerProvider, DefaultGuard) {
+    let exporter = InMemorySpanExporter::default();
+    let provider = SdkTracerProvider::builder()
+        .with_simple_exporter(exporter.clone())
+        .build();
+    let guard = tracing_subscriber::registry()
+        .with(tracing_opentelemetry::layer().with_tracer(provider.tracer(TRACER_NAME)))
+        .set_default();
+    (exporter, provider, guard)
+}
+
+fn span<'a>(spans: &'a [SpanData], name: &str) -> &'a SpanData {
+    spans
+        .iter()
+        .find(|s| s.name == name)
+        .unwrap_or_else(|| panic!("no {name} span"))
+}
+
+#[test]
+fn test_trace_env_empty_without_exporter() {
+    let span = info_span!("script");
+    let _entered = span.enter();
+    assert!(trace_env().is_empty());
+}
+
+#[test]
+fn test_trace_env_continues_current_span() {
+    let (exporter, provider, _guard) = collector();
+
+    let env = {
+        let span = info_span!("script");
+        let _entered = span.enter();
+        trace_env()
+    };
+    provider.force_flush().unwrap();
+
+    let spans = exporter.get_finished_spans().unwrap();
+    let context = &span(&spans, "script").span_context;
+    assert_eq!(
+        env,
+        [(
+            TRACEPARENT,
+            format!("00-{}-{}-01", context.trace_id(), context.span_id())
+        )]
+    );
+}
+
+#[tokio::test]
+async fn test_reconcile_exports_spans() {
+    let (exporter, provider, _guard) = collector();
+    let config: Config = serde_yaml::from_str(
+        "name: cleaner\nversion: v1\nkind: ConfigMap\nfinalizer: example.com/cleanup\n",
+    )
+    .unwrap();
+    let harness = Harness::new(config, "mod.nu", StubExecutor::exit_code(0));
+    let obj = object_from_yaml(
+        "apiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: settings\n  namespace: team-a\n  resourceVersion: \"1\"\n",
+    )
+    .unwrap();
+    harness.insert(&obj);
+
+    harness.reconcile(&obj).await.unwrap();
+    let stored = harness.get(Some("team-a"), "settings").unwrap();
+    harness.reconcile(&stored).await.unwrap();
+    provider.force_flush().unwrap();
+
+    let spans = exporter.get_finished_spans().unwrap();
+    let reconciles: Vec<_> = spans.iter().filter(|s| s.name == "reconcile").collect();
+    assert_eq!(reconciles.len(), 2);
+
+    let add_finalizer = span(&spans, "add_finalizer");
+    let script = span(&spans, "script");
+    assert!(
+        reconciles
+            .iter()
+            .any(|r| r.span_context.span_id() == add_finalizer.parent_span_id)
+    );
+    assert!(
+        reconciles
+            .iter()
+            .any(|r| r.span_context.span_id() == script.parent_span_id)
+    );
+    assert!(
+        script
+            .attributes
+            .iter()
+            .any(|kv| kv.key.as_str() == "exit_code" && kv.value.as_str() == "0")
+    );
+}
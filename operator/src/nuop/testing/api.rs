@@ -0,0 +1,400 @@
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+use http::{Request, Response, StatusCode};
+use kube::{
+    Client, Resource, ResourceExt,
+    api::{ApiResource, DynamicObject},
+    client::Body,
+};
+use serde_json::{Value, json};
+use tower_test::mock;
+
+/// A request received by the [`FakeApiServer`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    pub query: Option<String>,
+    pub content_type: Option<String>,
+    pub body: Option<Value>,
+}
+
+/// The finalizers of an object before and after a patch.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FinalizerPatch {
+    pub path: String,
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+}
+
+impl FinalizerPatch {
+    fn names(&self, name: &str) -> bool {
+        self.path.rsplit('/').next() == Some(name)
+    }
+}
+
+#[derive(Default)]
+struct ServerState {
+    /// Objects by their URL path
+    objects: BTreeMap<String, Value>,
+    /// Canned responses by method and path, taking precedence over objects
+    responses: Vec<(String, String, StatusCode, Value)>,
+    requests: Vec<RecordedRequest>,
+    finalizer_patches: Vec<FinalizerPatch>,
+}
+
+/// An in-memory API server behind a [`Client`], in the style of the crate's
+/// `tower_test` based tests. It serves the objects it holds, applies JSON,
+/// merge and apply patches to them, checks `test` operations and records
+/// every request.
+#[derive(Clone, Default)]
+pub struct FakeApiServer {
+    state: Arc<Mutex<ServerState>>,
+}
+
+impl FakeApiServer {
+    /// Starts the server on the current Tokio runtime and returns a client
+    /// connected to it.
+    pub fn start() -> (Client, Self) {
+        let (service, mut handle) = mock::pair::<Request<Body>, Response<Body>>();
+        let server = Self::default();
+
+        let state = server.state.clone();
+        tokio::spawn(async move {
+            while let Some((request, send)) = handle.next_request().await {
+                let (parts, body) = request.into_parts();
+                let body = body.collect_bytes().await.unwrap_or_default();
+                let recorded = RecordedRequest {
+                    method: parts.method.to_string(),
+                    path: parts.uri.path().to_string(),
+                    query: parts.uri.query().map(str::to_string),
+                    content_type: parts
+                        .headers
+                        .get(http::header::CONTENT_TYPE)
+                        .and_then(|v| v.to_str().ok())
+                        .map(str::to_string),
+                    body: serde_json::from_slice(&body).ok(),
+                };
+
+                let (status, body) = state
+                    .lock()
+                    .expect("fake API server lock poisoned")
+                    .handle(recorded);
+                let response = Response::builder()
+                    .status(status)
+                    .header(http::header::CONTENT_TYPE, "application/json")
+                    .body(Body::from(serde_json::to_vec(&body).unwrap_or_default()))
+                    .expect("valid response");
+                send.send_response(response);
+            }
+        });
+
+        (Client::new(service, "default"), server)
+    }
+
+    /// Stores an object, served at the URL of its kind.
+    pub fn insert(&self, api_resource: &ApiResource, obj: &DynamicObject) {
+        let path = object_path(api_resource, obj.namespace().as_deref(), &obj.name_any());
+        let value = serde_json::to_value(obj).expect("serializable object");
+        self.lock().objects.insert(path, value);
+    }
+
+    /// The stored object of a kind, reflecting patches applied so far.
+    pub fn get(
+        &self,
+        api_resource: &ApiResource,
+        namespace: Option<&str>,
+        name: &str,
+    ) -> Option<DynamicObject> {
+        let path = object_path(api_resource, namespace, name);
+        let value = self.lock().objects.get(&path).cloned()?;
+        serde_json::from_value(value).ok()
+    }
+
+    /// Answers requests with the given method and path with a fixed response.
+    pub fn respond(&self, method: &str, path: &str, status: u16, body: Value) {
+        let status = StatusCode::from_u16(status).expect("valid status code");
+        self.lock()
+            .responses
+            .push((method.to_string(), path.to_string(), status, body));
+    }
+
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.lock().requests.clone()
+    }
+
+    /// Requests that modified objects, i.e. everything but reads.
+    pub fn writes(&self) -> Vec<RecordedRequest> {
+        self.requests()
+            .into_iter()
+            .filter(|r| r.method != "GET")
+            .collect()
+    }
+
+    /// Finalizer changes made by JSON patches, in order.
+    pub fn finalizer_patches(&self) -> Vec<FinalizerPatch> {
+        self.lock().finalizer_patches.clone()
+    }
+
+    /// Asserts that a patch added `finalizer` to the object `name`.
+    pub fn assert_finalizer_added(&self, name: &str, finalizer: &str) {
+        let patches = self.finalizer_patches();
+        assert!(
+            patches.iter().any(|p| p.names(name)
+                && !p.before.iter().any(|f| f == finalizer)
+                && p.after.iter().any(|f| f == finalizer)),
+            "expected finalizer {finalizer} to be added to {name}, finalizer patches: {patches:?}"
+        );
+    }
+
+    /// Asserts that a patch removed `finalizer` from the object `name`.
+    pub fn assert_finalizer_removed(&self, name: &str, finalizer: &str) {
+        let patches = self.finalizer_patches();
+        assert!(
+            patches.iter().any(|p| p.names(name)
+                && p.before.iter().any(|f| f == finalizer)
+                && !p.after.iter().any(|f| f == finalizer)),
+            "expected finalizer {finalizer} to be removed from {name}, finalizer patches: {patches:?}"
+        );
+    }
+
+    pub fn assert_no_writes(&self) {
+        let writes = self.writes();
+        assert!(writes.is_empty(), "expected no writes, got {writes:?}");
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, ServerState> {
+        self.state.lock().expect("fake API server lock poisoned")
+    }
+}
+
+impl ServerState {
+    fn handle(&mut self, request: RecordedRequest) -> (StatusCode, Value) {
+        self.requests.push(request.clone());
+
+        if let Some((_, _, status, body)) = self
+            .responses
+            .iter()
+            .find(|(method, path, _, _)| *method == request.method && *path == request.path)
+        {
+            return (*status, body.clone());
+        }
+
+        let path = request.path.as_str();
+        match request.method.as_str() {
+            "GET" if self.objects.contains_key(path) => {
+                (StatusCode::OK, self.objects[path].clone())
+            }
+            "GET" => match self.list(path) {
+                Some(list) => (StatusCode::OK, list),
+                None => not_found(path),
+            },
+            "DELETE" => match self.objects.remove(path) {
+                Some(obj) => (StatusCode::OK, obj),
+                None => not_found(path),
+            },
+            "PATCH" => self.patch(path, &request),
+            "POST" | "PUT" => {
+                let obj = request.body.unwrap_or_default();
+                let path = match request.method.as_str() {
+                    "POST" => format!(
+                        "{path}/{}",
+                        obj["metadata"]["name"].as_str().unwrap_or_default()
+                    ),
+                    _ => path.to_string(),
+                };
+                self.store(path, obj)
+            }
+            _ => status(StatusCode::METHOD_NOT_ALLOWED, "MethodNotAllowed", path),
+        }
+    }
+
+    /// Lists the stored objects under a collection path.
+    fn list(&self, path: &str) -> Option<Value> {
+        if !is_collection(path) {
+            return None;
+        }
+        let prefix = format!("{path}/");
+        let items: Vec<_> = self
+            .objects
+            .iter()
+            .filter(|(p, _)| {
+                p.strip_prefix(&prefix)
+                    .is_some_and(|rest| !rest.contains('/'))
+            })
+            .map(|(_, obj)| obj.clone())
+            .collect();
+        Some(json!({
+            "kind": "List",
+            "apiVersion": "v1",
+            "metadata": { "resourceVersion": "1" },
+            "items": items
+        }))
+    }
+
+    fn patch(&mut self, path: &str, request: &RecordedRequest) -> (StatusCode, Value) {
+        let body = request.body.clone().unwrap_or_default();
+        let content_type = request.content_type.as_deref().unwrap_or_default();
+        let current = self.objects.get(path).cloned();
+
+        let patched = if content_type.starts_with("application/json-patch") {
+            let Some(current) = current else {
+                return not_found(path);
+            };
+            let mut obj = current.clone();
+            for op in body.as_array().into_iter().flatten() {
+                if let Err(cause) = apply_operation(&mut obj, op) {
+                    // The API server only names the failed operation in the details
+                    let (code, mut body) = status(
+                        StatusCode::UNPROCESSABLE_ENTITY,
+                        "Invalid",
+                        "the server rejected our request due to an error in our request",
+                    );
+                    body["details"] = json!({ "causes": [{ "message": cause }] });
+                    return (code, body);
+                }
+            }
+            if obj["metadata"]["finalizers"] != current["metadata"]["finalizers"] {
+                self.finalizer_patches.push(FinalizerPatch {
+                    path: path.to_string(),
+                    before: finalizers(&current),
+                    after: finalizers(&obj),
+                });
+            }
+            obj
+        } else {
+            // Merge, strategic and apply patches are all treated as merges
+            let is_apply = content_type.starts_with("application/apply-patch");
+            let mut obj = match current {
+                Some(obj) => obj,
+                None if is_apply => json!({}),
+                None => return not_found(path),
+            };
+            merge(&mut obj, &body);
+            obj
+        };
+        self.store(path.to_string(), patched)
+    }
+
+    fn store(&mut self, path: String, mut obj: Value) -> (StatusCode, Value) {
+        let version = self
+            .objects
+            .get(&path)
+            .and_then(|o| {
+                o["metadata"]["resourceVersion"]
+                    .as_str()?
+                    .parse::<u64>()
+                    .ok()
+            })
+            .unwrap_or(0);
+        if let Some(metadata) = obj.get_mut("metadata").and_then(Value::as_object_mut) {
+            metadata.insert("resourceVersion".into(), json!((version + 1).to_string()));
+        }
+        self.objects.insert(path, obj.clone());
+        (StatusCode::OK, obj)
+    }
+}
+
+/// The URL path of a namespaced or cluster-scoped object.
+pub(crate) fn object_path(
+    api_resource: &ApiResource,
+    namespace: Option<&str>,
+    name: &str,
+) -> String {
+    format!(
+        "{}/{}",
+        DynamicObject::url_path(api_resource, namespace),
+        name
+    )
+}
+
+/// Whether a path names a collection rather than an object, e.g.
+/// `/api/v1/namespaces/default/secrets` or `/apis/apps/v1/deployments`.
+fn is_collection(path: &str) -> bool {
+    let segments: Vec<_> = path.trim_start_matches('/').split('/').collect();
+    let resource = match segments.as_slice() {
+        ["api", _version, rest @ ..] | ["apis", _, _version, rest @ ..] => rest,
+        _ => return false,
+    };
+    match resource {
+        ["namespaces", _, rest @ ..] if !rest.is_empty() => rest.len() == 1,
+        rest => rest.len() == 1,
+    }
+}
+
+/// Applies one JSON patch operation, supporting `test`, `add`, `replace` and
+/// `remove` on object fields.
+fn apply_operation(obj: &mut Value, op: &Value) -> Result<(), String> {
+    let path = op["path"].as_str().ok_or("operation without a path")?;
+    let (parent, key) = path.rsplit_once('/').ok_or("invalid patch path")?;
+    let key = key.replace("~1", "/").replace("~0", "~");
+
+    match op["op"].as_str() {
+        Some("test") if obj.pointer(path) == Some(&op["value"]) => Ok(()),
+        Some("test") => Err(format!("test operation on {path} failed")),
+        Some(kind @ ("add" | "replace" | "remove")) => {
+            let parent = obj
+                .pointer_mut(parent)
+                .and_then(Value::as_object_mut)
+                .ok_or_else(|| format!("no object at {parent}"))?;
+            if kind == "remove" {
+                parent
+                    .remove(&key)
+                    .map(drop)
+                    .ok_or_else(|| format!("no value at {path}"))
+            } else {
+                parent.insert(key, op["value"].clone());
+                Ok(())
+            }
+        }
+        other => Err(format!("unsupported patch operation {other:?}")),
+    }
+}
+
+/// JSON merge patch, see RFC 7386.
+fn merge(target: &mut Value, patch: &Value) {
+    let Some(patch) = patch.as_object() else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = json!({});
+    }
+    let target = target.as_object_mut().expect("object");
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(key);
+        } else {
+            merge(target.entry(key.clone()).or_insert(Value::Null), value);
+        }
+    }
+}
+
+fn finalizers(obj: &Value) -> Vec<String> {
+    serde_json::from_value(obj["metadata"]["finalizers"].clone()).unwrap_or_default()
+}
+
+fn not_found(path: &str) -> (StatusCode, Value) {
+    status(
+        StatusCode::NOT_FOUND,
+        "NotFound",
+        &format!("{path} not found"),
+    )
+}
+
+fn status(code: StatusCode, reason: &str, message: &str) -> (StatusCode, Value) {
+    (
+        code,
+        json!({
+            "kind": "Status",
+            "apiVersion": "v1",
+            "status": "Failure",
+            "message": message,
+            "reason": reason,
+            "code": code.as_u16()
+        }),
+    )
+}
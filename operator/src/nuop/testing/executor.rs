@@ -0,0 +1,128 @@
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use async_trait::async_trait;
+
+use crate::nuop::reconciler::state::{
+    CommandExecutor, CommandResult, InvocationContext, ProcessExecutor,
+};
+
+/// A script invocation seen by a [`RecordingExecutor`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Invocation {
+    pub command: String,
+    pub input: String,
+    pub context: InvocationContext,
+    /// Exit code of the script, `None` when it could not be run
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+}
+
+/// Replies to every invocation with a fixed exit code and output, for tests
+/// of the reconciler's handling of script results without running Nushell.
+#[derive(Clone, Debug, Default)]
+pub struct StubExecutor {
+    pub exit_code: i32,
+    pub stdout: String,
+}
+
+impl StubExecutor {
+    pub fn exit_code(exit_code: i32) -> Self {
+        Self {
+            exit_code,
+            ..Default::default()
+        }
+    }
+}
+
+#[async_trait]
+impl CommandExecutor for StubExecutor {
+    async fn execute(
+        &self,
+        _script: &Path,
+        _command: &str,
+        _input: &str,
+        _context: &InvocationContext,
+    ) -> Result<CommandResult, anyhow::Error> {
+        Ok(CommandResult {
+            exit_code: self.exit_code,
+            stdout: self.stdout.clone(),
+            stderr: String::new(),
+        })
+    }
+}
+
+/// Records every invocation before handing it to the wrapped executor, by
+/// default a [`ProcessExecutor`] running the real script.
+#[derive(Clone, Default)]
+pub struct RecordingExecutor<E = ProcessExecutor>
+where
+    E: CommandExecutor,
+{
+    inner: E,
+    invocations: Arc<Mutex<Vec<Invocation>>>,
+}
+
+impl<E> RecordingExecutor<E>
+where
+    E: CommandExecutor,
+{
+    pub fn new(inner: E) -> Self {
+        Self {
+            inner,
+            invocations: Default::default(),
+        }
+    }
+
+    pub fn invocations(&self) -> Vec<Invocation> {
+        self.invocations
+            .lock()
+            .expect("invocations lock poisoned")
+            .clone()
+    }
+
+    /// The commands run so far, e.g. `["reconcile", "finalize"]`.
+    pub fn commands(&self) -> Vec<String> {
+        self.invocations()
+            .into_iter()
+            .map(|invocation| invocation.command)
+            .collect()
+    }
+
+    pub fn assert_commands(&self, expected: &[&str]) {
+        assert_eq!(self.commands(), expected, "unexpected script commands");
+    }
+}
+
+#[async_trait]
+impl<E> CommandExecutor for RecordingExecutor<E>
+where
+    E: CommandExecutor,
+{
+    async fn execute(
+        &self,
+        script: &Path,
+        command: &str,
+        input: &str,
+        context: &InvocationContext,
+    ) -> Result<CommandResult, anyhow::Error> {
+        let result = self.inner.execute(script, command, input, context).await;
+        let (exit_code, stdout) = match &result {
+            Ok(result) => (Some(result.exit_code), result.stdout.clone()),
+            Err(_) => (None, String::new()),
+        };
+        self.invocations
+            .lock()
+            .expect("invocations lock poisoned")
+            .push(Invocation {
+                command: command.to_string(),
+                input: input.to_string(),
+                context: context.clone(),
+                exit_code,
+                stdout,
+            });
+        result
+    }
+}
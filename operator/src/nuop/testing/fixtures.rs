@@ -0,0 +1,45 @@
+use std::{fs, path::Path};
+
+use anyhow::Context;
+use kube::api::DynamicObject;
+use serde::Deserialize;
+
+/// Parses the objects of a YAML or JSON document, which may hold several
+/// YAML documents separated by `---`.
+pub fn objects_from_yaml(yaml: &str) -> anyhow::Result<Vec<DynamicObject>> {
+    serde_yaml::Deserializer::from_str(yaml)
+        .map(|document| {
+            let value = serde_yaml::Value::deserialize(document)?;
+            Ok(value)
+        })
+        .filter(|value: &anyhow::Result<serde_yaml::Value>| {
+            !matches!(value, Ok(serde_yaml::Value::Null))
+        })
+        .map(|value| Ok(serde_yaml::from_value(value?)?))
+        .collect()
+}
+
+/// Parses a single object from YAML or JSON.
+pub fn object_from_yaml(yaml: &str) -> anyhow::Result<DynamicObject> {
+    let mut objects = objects_from_yaml(yaml)?;
+    match objects.len() {
+        1 => Ok(objects.remove(0)),
+        n => anyhow::bail!("expected one object, found {n}"),
+    }
+}
+
+/// Loads the objects of a fixture file.
+pub fn load_objects(path: impl AsRef<Path>) -> anyhow::Result<Vec<DynamicObject>> {
+    let path = path.as_ref();
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read fixture {path:?}"))?;
+    objects_from_yaml(&content).with_context(|| format!("Invalid fixture {path:?}"))
+}
+
+/// Loads a fixture file holding a single object.
+pub fn load_object(path: impl AsRef<Path>) -> anyhow::Result<DynamicObject> {
+    let path = path.as_ref();
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read fixture {path:?}"))?;
+    object_from_yaml(&content).with_context(|| format!("Invalid fixture {path:?}"))
+}
@@ -0,0 +1,94 @@
+use std::{path::PathBuf, sync::Arc, time::Duration};
+
+use kube::{
+    Error,
+    api::{ApiResource, DynamicObject},
+    runtime::controller::Action,
+};
+
+use crate::nuop::reconciler::{
+    config::Config,
+    controller::{error_policy, reconcile},
+    state::{CommandExecutor, ProcessExecutor, State},
+    util::get_script_config,
+};
+
+use super::{api::FakeApiServer, executor::RecordingExecutor};
+
+/// Runs a script through the operator's reconciler against a
+/// [`FakeApiServer`], recording every script invocation.
+pub struct Harness<E = ProcessExecutor>
+where
+    E: CommandExecutor,
+{
+    pub state: Arc<State<RecordingExecutor<E>>>,
+    pub api: FakeApiServer,
+}
+
+impl Harness<ProcessExecutor> {
+    /// Loads the config of a Nushell script and runs it with `nu`.
+    pub fn for_script(script: impl Into<PathBuf>) -> anyhow::Result<Self> {
+        let script = script.into();
+        let config = get_script_config(&script)?;
+        Ok(Self::new(config, script, ProcessExecutor::default()))
+    }
+}
+
+impl<E> Harness<E>
+where
+    E: CommandExecutor,
+{
+    /// Must be called within a Tokio runtime, which serves the fake API.
+    pub fn new(config: Config, script: impl Into<PathBuf>, executor: E) -> Self {
+        let (client, api) = FakeApiServer::start();
+        let api_resource = ApiResource::from_gvk(&(&config).into());
+        let state = State::new(
+            api_resource,
+            client,
+            config,
+            script.into(),
+            RecordingExecutor::new(executor),
+        );
+        Self {
+            state: Arc::new(state),
+            api,
+        }
+    }
+
+    /// Stores an object of the script's kind on the fake API server, where
+    /// the reconciler patches it.
+    pub fn insert(&self, obj: &DynamicObject) {
+        self.api.insert(&self.state.api_resource, obj);
+    }
+
+    /// The stored object, reflecting the patches applied by the reconciler.
+    pub fn get(&self, namespace: Option<&str>, name: &str) -> Option<DynamicObject> {
+        self.api.get(&self.state.api_resource, namespace, name)
+    }
+
+    /// Reconciles the object once, as the controller would on a watch event.
+    pub async fn reconcile(&self, obj: &DynamicObject) -> Result<Action, Error> {
+        reconcile(Arc::new(obj.clone()), self.state.clone()).await
+    }
+
+    /// The action the controller takes after a failed reconcile.
+    pub fn error_policy(&self, obj: &DynamicObject, err: &Error) -> Action {
+        error_policy(Arc::new(obj.clone()), err, self.state.clone())
+    }
+
+    pub fn executor(&self) -> &RecordingExecutor<E> {
+        &self.state.executor
+    }
+}
+
+pub fn assert_requeue(action: &Action, after: Duration) {
+    assert_eq!(
+        *action,
+        Action::requeue(after),
+        "expected a requeue after {after:?}"
+    );
+}
+
+pub fn assert_await_change(action: &Action) {
+    assert_eq!(*action, Action::await_change(), "expected to await changes");
+}
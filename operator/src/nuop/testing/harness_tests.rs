@@ -0,0 +1,116 @@
+use std::time::Duration;
+
+use k8s_openapi::{apimachinery::pkg::apis::meta::v1::Time, chrono::Utc};
+use kube::ResourceExt;
+use serde_json::json;
+
+use super::{
+    Config, Harness, StubExecutor, assert_await_change, assert_requeue, object_from_yaml,
+    objects_from_yaml,
+};
+
+const FINALIZER: &str = "example.com/cleanup";
+
+fn config() -> Config {
+    serde_yaml::from_str(&format!(
+        "name: cleaner\nversion: v1\nkind: ConfigMap\nfinalizer: {FINALIZER}\nrequeue_after_noop: 60\n"
+    ))
+    .unwrap()
+}
+
+fn config_map() -> kube::api::DynamicObject {
+    object_from_yaml(
+        "apiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: settings\n  namespace: team-a\n  resourceVersion: \"7\"\ndata:\n  mode: fast\n",
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_harness_adds_finalizer_then_reconciles() {
+    let harness = Harness::new(config(), "mod.nu", StubExecutor::exit_code(0));
+    harness.insert(&config_map());
+
+    let action = harness.reconcile(&config_map()).await.unwrap();
+    assert_requeue(&action, Duration::from_secs(5));
+    harness.api.assert_finalizer_added("settings", FINALIZER);
+    harness.executor().assert_commands(&[]);
+
+    let stored = harness.get(Some("team-a"), "settings").unwrap();
+    assert_eq!(stored.finalizers(), [FINALIZER]);
+    assert_eq!(stored.resource_version().as_deref(), Some("8"));
+
+    let action = harness.reconcile(&stored).await.unwrap();
+    assert_requeue(&action, Duration::from_secs(60));
+    harness.executor().assert_commands(&["reconcile"]);
+    assert!(
+        harness.executor().invocations()[0]
+            .input
+            .contains("mode: fast")
+    );
+}
+
+#[tokio::test]
+async fn test_harness_finalizes_deleted_object() {
+    let harness = Harness::new(config(), "mod.nu", StubExecutor::exit_code(0));
+    let mut obj = config_map();
+    obj.metadata.finalizers = Some(vec![FINALIZER.to_string()]);
+    obj.metadata.deletion_timestamp = Some(Time(Utc::now()));
+    harness.insert(&obj);
+
+    let action = harness.reconcile(&obj).await.unwrap();
+    assert_await_change(&action);
+    harness.executor().assert_commands(&["finalize"]);
+    harness.api.assert_finalizer_removed("settings", FINALIZER);
+    assert!(
+        harness
+            .get(Some("team-a"), "settings")
+            .unwrap()
+            .finalizers()
+            .is_empty()
+    );
+}
+
+#[tokio::test]
+async fn test_harness_reports_script_failures() {
+    let harness = Harness::new(config(), "mod.nu", StubExecutor::exit_code(1));
+    let mut obj = config_map();
+    obj.metadata.finalizers = Some(vec![FINALIZER.to_string()]);
+    harness.insert(&obj);
+
+    let err = harness.reconcile(&obj).await.unwrap_err();
+    assert_requeue(&harness.error_policy(&obj, &err), Duration::from_secs(300));
+    assert_eq!(harness.executor().invocations()[0].exit_code, Some(1));
+    harness.api.assert_no_writes();
+}
+
+#[tokio::test]
+async fn test_fake_api_rejects_stale_finalizer_patch() {
+    let harness = Harness::new(config(), "mod.nu", StubExecutor::exit_code(0));
+    let mut stored = config_map();
+    stored.metadata.resource_version = Some("9".to_string());
+    harness.insert(&stored);
+
+    // The reconciler retries with the current object after the failed test
+    let action = harness.reconcile(&config_map()).await.unwrap();
+    assert_requeue(&action, Duration::from_secs(5));
+
+    let patches: Vec<_> = harness
+        .api
+        .writes()
+        .into_iter()
+        .map(|r| r.body.unwrap()[0]["value"].clone())
+        .collect();
+    assert_eq!(patches, [json!("7"), json!("9")]);
+    assert_eq!(harness.api.finalizer_patches().len(), 1);
+}
+
+#[test]
+fn test_objects_from_multi_document_yaml() {
+    let objects = objects_from_yaml(
+        "---\napiVersion: v1\nkind: Namespace\nmetadata:\n  name: a\n---\n{\"apiVersion\": \"v1\", \"kind\": \"Namespace\", \"metadata\": {\"name\": \"b\"}}\n",
+    )
+    .unwrap();
+    let names: Vec<_> = objects.iter().map(|o| o.name_any()).collect();
+    assert_eq!(names, ["a", "b"]);
+    assert!(object_from_yaml("").is_err());
+}
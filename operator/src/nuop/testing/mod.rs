@@ -0,0 +1,41 @@
+//! Helpers for testing Nushell scripts against the real reconciler, enabled
+//! with the `testing` feature:
+//!
+//! ```no_run
+//! # async fn example() -> anyhow::Result<()> {
+//! use operator::nuop::testing::{Harness, assert_requeue, load_object};
+//! use std::time::Duration;
+//!
+//! let harness = Harness::for_script("scripts/my-operator/mod.nu")?;
+//! let obj = load_object("tests/fixtures/configmap.yaml")?;
+//! harness.insert(&obj);
+//!
+//! let action = harness.reconcile(&obj).await?;
+//! assert_requeue(&action, Duration::from_secs(5));
+//! harness.api.assert_finalizer_added("my-config", "example.com/finalizer");
+//!
+//! let obj = harness.get(Some("default"), "my-config").unwrap();
+//! harness.reconcile(&obj).await?;
+//! harness.executor().assert_commands(&["reconcile"]);
+//! # Ok(())
+//! # }
+//! ```
+
+mod api;
+mod executor;
+mod fixtures;
+mod harness;
+
+pub use api::{FakeApiServer, FinalizerPatch, RecordedRequest};
+pub use executor::{Invocation, RecordingExecutor, StubExecutor};
+pub use fixtures::{load_object, load_objects, object_from_yaml, objects_from_yaml};
+pub use harness::{Harness, assert_await_change, assert_requeue};
+
+pub use crate::nuop::reconciler::{
+    config::Config,
+    controller::{error_policy, reconcile},
+    state::{CommandExecutor, CommandResult, InvocationContext, ProcessExecutor, State},
+};
+
+#[cfg(test)]
+mod harness_tests;